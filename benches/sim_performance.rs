@@ -1,6 +1,6 @@
 use std::fmt::{Display, Formatter};
 
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 
 use discrete_event_simulator::{
     environment::bus_world::{
@@ -59,6 +59,7 @@ fn incrementing_configs(c: &mut Criterion) {
         30,   // drop_off_delay
         1200, // next_stop_delay
         600,  // initial_delay
+        0,    // seed
     );
 
     for i in 1..=100 {
@@ -78,7 +79,7 @@ fn incrementing_configs(c: &mut Criterion) {
         group.bench_with_input(
             BenchmarkId::from_parameter(format!("{}", i * 10)),
             &i,
-            |b, &i| b.iter(|| run_sim_from_config(config)),
+            |b, &_i| b.iter(|| run_sim_from_config(config)),
         );
     }
 }
@@ -89,14 +90,15 @@ fn run_sim_from_config(config: Configuration) -> Option<String> {
     let init_event = Box::new(NewBusEvent::new(
         0, // uid
         0, // timestamp for first event
-        serde_json::to_string(&buses).unwrap(),
+        buses,
     ));
 
     env.create_bus_stops(config.bus_stops);
-    env.initialize_bus_stops_with_passengers(config.passenger_count);
+    env.initialize_bus_stops_with_passengers(config.passenger_count)
+        .ok()?;
 
     let mut sim = Simulation::new(config.runtime, Box::new(env), init_event);
-    sim.run();
+    sim.run().ok()?;
     if let Some(event_count_series) = sim
         .statistics
         .get_series_by_name(String::from("Events Ran"))
@@ -1,43 +1,382 @@
 // Discrete Event Simulator
 //
-//
-//
+// Events are ordered by timestamp, then by priority (lower first), and
+// finally by the sequence number assigned when they were scheduled. This
+// gives a total order, so events sharing a timestamp and priority are
+// always processed in FIFO order regardless of the heap's internals.
 
 use crate::event::event::Event;
-use std::collections::BinaryHeap;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Identifies an event that has been handed to the [Scheduler], so that
+/// it can later be cancelled or moved to a different time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct EventHandle(u64);
+
+/// Ordering key for an event in the queue. The event itself lives in
+/// [Scheduler]'s pending map, so cancelled or rescheduled events leave a
+/// stale key behind which is skipped when popped (lazy deletion).
+pub struct ScheduledEvent {
+    pub timestamp: usize,
+    pub priority: i32,
+    pub sequence: u64,
+}
+
+impl ScheduledEvent {
+    fn key(&self) -> (usize, i32, u64) {
+        (self.timestamp, self.priority, self.sequence)
+    }
+}
+
+impl Ord for ScheduledEvent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap, so reverse to pop the earliest key first
+        self.key().cmp(&other.key()).reverse()
+    }
+}
 
-pub struct Scheduler {
+impl PartialOrd for ScheduledEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ScheduledEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for ScheduledEvent {}
+
+pub struct Scheduler<P> {
     pub current_time: usize,
     pub runtime: usize,
-    pub event_queue: BinaryHeap<Box<dyn Event>>,
+    event_queue: BinaryHeap<ScheduledEvent>,
+    pending: HashMap<u64, Box<dyn Event<P>>>,
+    next_sequence: u64,
+    recently_scheduled: Vec<u64>,
 }
 
-impl Scheduler {
-    pub fn new(runtime: usize) -> Scheduler {
+impl<P> Scheduler<P> {
+    pub fn new(runtime: usize) -> Scheduler<P> {
         Scheduler {
             current_time: 0,
             runtime,
-            event_queue: BinaryHeap::<Box<dyn Event>>::new(),
+            event_queue: BinaryHeap::<ScheduledEvent>::new(),
+            pending: HashMap::new(),
+            next_sequence: 0,
+            recently_scheduled: Vec::new(),
         }
     }
 
-    pub fn add_event(&mut self, event: Box<dyn Event>) {
+    /// Rebuild a scheduler from a checkpoint. Events keep their handles,
+    /// so handles held before the checkpoint stay valid after restoring.
+    pub fn restore(
+        runtime: usize,
+        current_time: usize,
+        next_sequence: u64,
+        events: Vec<(EventHandle, Box<dyn Event<P>>)>,
+    ) -> Scheduler<P> {
+        let mut scheduler = Scheduler::new(runtime);
+        scheduler.current_time = current_time;
+        scheduler.next_sequence = next_sequence;
+        for (handle, event) in events {
+            scheduler.event_queue.push(ScheduledEvent {
+                timestamp: event.get_time_stamp(),
+                priority: event.get_priority(),
+                sequence: handle.0,
+            });
+            scheduler.next_sequence = scheduler.next_sequence.max(handle.0 + 1);
+            scheduler.pending.insert(handle.0, event);
+        }
+        scheduler
+    }
+
+    /// The sequence number the next scheduled event will receive.
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    pub fn add_event(&mut self, event: Box<dyn Event<P>>) -> EventHandle {
         // somwhere we need to add logic for the delay of running the event vs
         // the absolute current time in the simulation that the event takes place.
-        self.event_queue.push(event);
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.event_queue.push(ScheduledEvent {
+            timestamp: event.get_time_stamp(),
+            priority: event.get_priority(),
+            sequence,
+        });
+        self.pending.insert(sequence, event);
+        self.recently_scheduled.push(sequence);
+        EventHandle(sequence)
     }
 
-    pub fn next_event(&mut self) -> Option<Box<dyn Event>> {
-        let event = match self.event_queue.pop() {
-            Some(event) => {
-                if event.get_time_stamp() > self.runtime {
-                    return None;
+    /// Remove a pending event from the queue. Returns the event if it had
+    /// not yet been processed or cancelled.
+    pub fn cancel(&mut self, handle: EventHandle) -> Option<Box<dyn Event<P>>> {
+        self.pending.remove(&handle.0)
+    }
+
+    /// Move a pending event to a new time. The event keeps its original
+    /// insertion order relative to other events at the new time.
+    pub fn reschedule(&mut self, handle: EventHandle, new_time: usize) -> Result<(), String> {
+        if new_time < self.current_time {
+            return Err(format!(
+                "Cannot reschedule to {} which is before the current time {}",
+                new_time, self.current_time
+            ));
+        }
+        let event = self
+            .pending
+            .get_mut(&handle.0)
+            .ok_or(format!("Event {:?} is not pending", handle))?;
+        event.set_time_stamp(new_time);
+        self.event_queue.push(ScheduledEvent {
+            timestamp: new_time,
+            priority: event.get_priority(),
+            sequence: handle.0,
+        });
+        self.recently_scheduled.push(handle.0);
+        Ok(())
+    }
+
+    /// Look at a pending event without removing it.
+    pub fn get_event(&self, handle: EventHandle) -> Option<&dyn Event<P>> {
+        self.pending.get(&handle.0).map(|event| event.as_ref())
+    }
+
+    /// Handles of every event added or rescheduled since the last call.
+    pub fn take_recently_scheduled(&mut self) -> Vec<EventHandle> {
+        self.recently_scheduled.drain(..).map(EventHandle).collect()
+    }
+
+    /// Check whether the event behind a handle is still waiting to be processed.
+    pub fn is_pending(&self, handle: EventHandle) -> bool {
+        self.pending.contains_key(&handle.0)
+    }
+
+    /// Every pending event, in the order they will be processed.
+    pub fn pending_events(&self) -> Vec<(EventHandle, &dyn Event<P>)> {
+        let mut events: Vec<(EventHandle, &dyn Event<P>)> = self
+            .pending
+            .iter()
+            .map(|(sequence, event)| (EventHandle(*sequence), event.as_ref()))
+            .collect();
+        events.sort_by_key(|(handle, event)| {
+            (event.get_time_stamp(), event.get_priority(), handle.0)
+        });
+        events
+    }
+
+    /// Number of events still waiting to be processed.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Timestamp of the next event that would be returned by [Scheduler::next_event],
+    /// without removing it. Stale keys at the top of the queue are discarded.
+    pub fn peek_time(&mut self) -> Option<usize> {
+        while let Some(scheduled) = self.event_queue.peek() {
+            match self.pending.get(&scheduled.sequence) {
+                Some(event) if event.get_time_stamp() == scheduled.timestamp => {
+                    return Some(scheduled.timestamp);
+                }
+                _ => {
+                    self.event_queue.pop();
                 }
-                self.current_time = event.get_time_stamp();
-                Some(event)
             }
-            None => None,
-        };
-        return event;
+        }
+        None
+    }
+
+    pub fn next_event(&mut self) -> Option<Box<dyn Event<P>>> {
+        while let Some(scheduled) = self.event_queue.pop() {
+            let is_current = match self.pending.get(&scheduled.sequence) {
+                Some(event) => event.get_time_stamp() == scheduled.timestamp,
+                None => false,
+            };
+            if !is_current {
+                // cancelled, or a stale key left behind by a reschedule
+                continue;
+            }
+            let event = self.pending.remove(&scheduled.sequence).unwrap();
+            if scheduled.timestamp > self.runtime {
+                return None;
+            }
+            self.current_time = scheduled.timestamp;
+            return Some(event);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::{Display, Error, Formatter};
+
+    use super::{EventHandle, Scheduler};
+    use crate::event::event::Event;
+
+    struct TestEvent {
+        uid: usize,
+        timestamp: usize,
+        priority: i32,
+    }
+
+    impl Event<()> for TestEvent {
+        fn get_event_type(&self) -> &str {
+            "Test"
+        }
+
+        fn get_uid(&self) -> usize {
+            self.uid
+        }
+
+        fn get_time_stamp(&self) -> usize {
+            self.timestamp
+        }
+
+        fn set_time_stamp(&mut self, timestamp: usize) {
+            self.timestamp = timestamp;
+        }
+
+        fn get_priority(&self) -> i32 {
+            self.priority
+        }
+
+        fn get_payload(&self) -> &() {
+            &()
+        }
+    }
+
+    impl Display for TestEvent {
+        fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+            write!(f, "TestEvent: uid: {}", self.uid)
+        }
+    }
+
+    fn drain_uids(scheduler: &mut Scheduler<()>) -> Vec<usize> {
+        let mut uids = Vec::new();
+        while let Some(event) = scheduler.next_event() {
+            uids.push(event.get_uid());
+        }
+        uids
+    }
+
+    #[test]
+    fn same_time_events_are_fifo() {
+        let mut scheduler = Scheduler::<()>::new(100);
+        for uid in 0..50 {
+            scheduler.add_event(Box::new(TestEvent {
+                uid,
+                timestamp: 5,
+                priority: 0,
+            }));
+        }
+        assert_eq!(drain_uids(&mut scheduler), (0..50).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn timestamp_then_priority_then_insertion() {
+        let mut scheduler = Scheduler::<()>::new(100);
+        let events = [(0, 10, 0), (1, 5, 1), (2, 5, 0), (3, 5, 1), (4, 1, 9)];
+        for (uid, timestamp, priority) in events {
+            scheduler.add_event(Box::new(TestEvent {
+                uid,
+                timestamp,
+                priority,
+            }));
+        }
+        assert_eq!(drain_uids(&mut scheduler), vec![4, 2, 1, 3, 0]);
+    }
+
+    fn test_event(uid: usize, timestamp: usize) -> Box<TestEvent> {
+        Box::new(TestEvent {
+            uid,
+            timestamp,
+            priority: 0,
+        })
+    }
+
+    #[test]
+    fn peek_skips_cancelled_events() {
+        let mut scheduler = Scheduler::<()>::new(100);
+        let handle = scheduler.add_event(test_event(0, 1));
+        scheduler.add_event(test_event(1, 4));
+        assert_eq!(scheduler.peek_time(), Some(1));
+        scheduler.cancel(handle);
+        assert_eq!(scheduler.peek_time(), Some(4));
+        assert_eq!(scheduler.next_event().unwrap().get_uid(), 1);
+        assert_eq!(scheduler.peek_time(), None);
+    }
+
+    #[test]
+    fn restore_keeps_handles_and_order() {
+        let mut scheduler = Scheduler::<()>::new(100);
+        scheduler.add_event(test_event(0, 3));
+        let handle = scheduler.add_event(test_event(1, 3));
+        scheduler.add_event(test_event(2, 1));
+        let pending: Vec<usize> = scheduler
+            .pending_events()
+            .iter()
+            .map(|(_, event)| event.get_uid())
+            .collect();
+        assert_eq!(pending, vec![2, 0, 1]);
+
+        let events: Vec<(EventHandle, Box<dyn Event<()>>)> = scheduler
+            .pending_events()
+            .iter()
+            .map(|(handle, event)| {
+                let copy: Box<dyn Event<()>> = test_event(event.get_uid(), event.get_time_stamp());
+                (*handle, copy)
+            })
+            .collect();
+        let mut restored = Scheduler::restore(100, 0, scheduler.next_sequence(), events);
+        restored.reschedule(handle, 2).unwrap();
+        assert_eq!(drain_uids(&mut restored), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn cancelled_event_never_fires() {
+        let mut scheduler = Scheduler::<()>::new(100);
+        scheduler.add_event(test_event(0, 1));
+        let handle = scheduler.add_event(test_event(1, 2));
+        scheduler.add_event(test_event(2, 3));
+        assert!(scheduler.is_pending(handle));
+        assert_eq!(scheduler.cancel(handle).unwrap().get_uid(), 1);
+        assert!(!scheduler.is_pending(handle));
+        assert!(scheduler.cancel(handle).is_none());
+        assert_eq!(drain_uids(&mut scheduler), vec![0, 2]);
+    }
+
+    #[test]
+    fn rescheduled_event_fires_once_at_new_time() {
+        let mut scheduler = Scheduler::<()>::new(100);
+        let handle = scheduler.add_event(test_event(0, 1));
+        scheduler.add_event(test_event(1, 5));
+        scheduler.reschedule(handle, 10).unwrap();
+        assert_eq!(scheduler.pending_count(), 2);
+
+        assert_eq!(scheduler.next_event().unwrap().get_uid(), 1);
+        let moved = scheduler.next_event().unwrap();
+        assert_eq!(moved.get_uid(), 0);
+        assert_eq!(moved.get_time_stamp(), 10);
+        assert_eq!(scheduler.current_time, 10);
+        assert!(scheduler.next_event().is_none());
+    }
+
+    #[test]
+    fn reschedule_rejects_past_and_processed_events() {
+        let mut scheduler = Scheduler::<()>::new(100);
+        let first = scheduler.add_event(test_event(0, 5));
+        let second = scheduler.add_event(test_event(1, 6));
+        scheduler.next_event();
+        assert!(scheduler.reschedule(first, 10).is_err());
+        assert!(scheduler.reschedule(second, 1).is_err());
+        assert!(scheduler.reschedule(second, 5).is_ok());
     }
 }
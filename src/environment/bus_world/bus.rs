@@ -1,49 +1,129 @@
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     fmt::{Display, Error, Formatter},
 };
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 
 use crate::{
     environment::bus_world::passenger::Passenger,
     genetic_learning::evolution::{Breedable, Dna, Fitness},
+    rng::sim_rng::new_uuid,
 };
 use serde_with::serde_as;
 
+/// What a bus does once it reaches the last stop of its route.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteKind {
+    /// Park at the last stop for the rest of the run.
+    #[default]
+    Once,
+    /// Drive on from the last stop to the first and around again.
+    Loop,
+    /// Turn around at either end and drive the route in reverse.
+    OutAndBack,
+    /// Drop everyone off at the last stop, then drive back to the first
+    /// stop, the depot, without picking anyone up.
+    ReturnToDepot,
+}
+
+impl RouteKind {
+    /// Every hop a bus makes between stops of `route`, including the hops
+    /// back to the start of a new trip.
+    pub fn hops<'a>(&self, route: &'a [String]) -> Vec<(&'a String, &'a String)> {
+        let mut hops: Vec<(&String, &String)> =
+            route.windows(2).map(|hop| (&hop[0], &hop[1])).collect();
+        match self {
+            RouteKind::Once => {}
+            RouteKind::Loop | RouteKind::ReturnToDepot => {
+                if let (Some(first), Some(last)) = (route.first(), route.last()) {
+                    if route.len() > 1 {
+                        hops.push((last, first));
+                    }
+                }
+            }
+            RouteKind::OutAndBack => {
+                let back: Vec<(&String, &String)> = route
+                    .windows(2)
+                    .rev()
+                    .map(|hop| (&hop[1], &hop[0]))
+                    .collect();
+                hops.extend(back);
+            }
+        }
+        hops
+    }
+}
+
+impl Display for RouteKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let name = match self {
+            RouteKind::Once => "once",
+            RouteKind::Loop => "loop",
+            RouteKind::OutAndBack => "out_and_back",
+            RouteKind::ReturnToDepot => "return_to_depot",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 #[serde_as]
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct Bus {
     pub uuid: String,
     #[serde_as(as = "Vec<(_, _)>")]
-    pub passengers: HashMap<String, Vec<Passenger>>,
+    pub passengers: BTreeMap<String, Vec<Passenger>>,
     pub serviced_stop_names: Vec<String>,
     current_stop: usize,
     pub capacity: usize,
+    #[serde(default)]
+    pub route_kind: RouteKind,
+    /// How long the bus waits before starting each trip after the first.
+    #[serde(default)]
+    pub layover: usize,
+    /// Driving the route backwards, on the way back of an out-and-back route.
+    #[serde(default)]
+    reversed: bool,
+    /// Trips finished so far.
+    #[serde(default)]
+    trips: usize,
 }
 
 impl Bus {
-    pub fn new(capacity: usize) -> Bus {
+    pub fn new<R: Rng + ?Sized>(capacity: usize, rng: &mut R) -> Bus {
         Bus {
-            uuid: Uuid::new_v4().to_string(),
-            passengers: HashMap::new(),
+            uuid: new_uuid(rng),
+            passengers: BTreeMap::new(),
             serviced_stop_names: Vec::new(),
             current_stop: 0,
             capacity,
+            route_kind: RouteKind::Once,
+            layover: 0,
+            reversed: false,
+            trips: 0,
         }
     }
 
+    pub fn with_route_kind(mut self, route_kind: RouteKind, layover: usize) -> Bus {
+        self.route_kind = route_kind;
+        self.layover = layover;
+        self
+    }
+
     pub fn reset(&mut self) {
         self.passengers.clear();
         self.serviced_stop_names.clear();
         self.current_stop = 0;
+        self.reversed = false;
+        self.trips = 0;
     }
 
+    /// Take a passenger on board, to get off where their current leg ends.
     pub fn add_passenger(&mut self, passenger: Passenger) {
         self.passengers
-            .entry(passenger.destination.clone())
+            .entry(passenger.leg_destination().clone())
             .or_default()
             .push(passenger);
     }
@@ -70,12 +150,94 @@ impl Bus {
         self.serviced_stop_names.get(self.current_stop)
     }
 
+    /// Move to the next stop, turning around or starting a new trip at
+    /// the end of the route as the route kind says.
     pub fn advance_to_next_stop(&mut self) {
-        self.current_stop += 1;
+        let Some(next) = self.next_index() else {
+            self.current_stop += 1;
+            return;
+        };
+        let last = self.serviced_stop_names.len() - 1;
+        self.current_stop = next;
+        match self.route_kind {
+            RouteKind::Once => {}
+            RouteKind::Loop | RouteKind::ReturnToDepot => {
+                if next == 0 {
+                    self.trips += 1;
+                }
+            }
+            RouteKind::OutAndBack => {
+                if next == last && !self.reversed {
+                    self.reversed = true;
+                    self.trips += 1;
+                } else if next == 0 && self.reversed {
+                    self.reversed = false;
+                    self.trips += 1;
+                }
+            }
+        }
     }
 
     pub fn get_next_stop(&self) -> Option<&String> {
-        self.serviced_stop_names.get(self.current_stop + 1)
+        self.next_index()
+            .and_then(|next| self.serviced_stop_names.get(next))
+    }
+
+    fn next_index(&self) -> Option<usize> {
+        let len = self.serviced_stop_names.len();
+        if len < 2 || self.current_stop >= len {
+            return None;
+        }
+        let at_end = self.current_stop + 1 == len;
+        match self.route_kind {
+            RouteKind::OutAndBack if self.reversed || at_end => self.current_stop.checked_sub(1),
+            _ if !at_end => Some(self.current_stop + 1),
+            RouteKind::Loop | RouteKind::ReturnToDepot => Some(0),
+            RouteKind::Once | RouteKind::OutAndBack => None,
+        }
+    }
+
+    /// Trips finished so far. An out-and-back route counts each direction.
+    pub fn trips(&self) -> usize {
+        self.trips
+    }
+
+    /// The bus has just finished a trip and is about to start another, so
+    /// it waits out its layover first.
+    pub fn layover_due(&self) -> bool {
+        let last = self.serviced_stop_names.len().saturating_sub(1);
+        let at_trip_start = match self.route_kind {
+            RouteKind::Once => false,
+            RouteKind::Loop | RouteKind::ReturnToDepot => self.current_stop == 0,
+            RouteKind::OutAndBack => {
+                (self.current_stop == 0 && !self.reversed)
+                    || (self.current_stop == last && self.reversed)
+            }
+        };
+        self.trips > 0 && at_trip_start
+    }
+
+    /// The stops still ahead on the current trip, the ones passengers
+    /// boarding here can ride to. Empty when the bus is out of service,
+    /// e.g. heading back to its depot.
+    pub fn current_trip_stops(&self) -> Vec<&String> {
+        let stops = &self.serviced_stop_names;
+        if self.current_stop >= stops.len() {
+            return Vec::new();
+        }
+        match self.route_kind {
+            RouteKind::Once | RouteKind::ReturnToDepot => {
+                stops[self.current_stop + 1..].iter().collect()
+            }
+            RouteKind::Loop => stops[self.current_stop + 1..]
+                .iter()
+                .chain(&stops[..self.current_stop])
+                .collect(),
+            RouteKind::OutAndBack if self.reversed => {
+                stops[..self.current_stop].iter().rev().collect()
+            }
+            RouteKind::OutAndBack => stops[self.current_stop + 1..].iter().collect(),
+        }
     }
 }
 
@@ -83,14 +245,14 @@ impl<T> Breedable<T> for Bus
 where
     T: Dna,
 {
-    fn reproduce(&self, other: &Bus) -> Result<Bus, String> {
+    fn reproduce<R: Rng + ?Sized>(&self, other: &Bus, rng: &mut R) -> Result<Bus, String> {
         let larger_capacity = if self.capacity > other.capacity {
             self.capacity
         } else {
             other.capacity
         };
-        let mut child = Bus::new(larger_capacity);
-        let rng = &mut thread_rng();
+        let mut child =
+            Bus::new(larger_capacity, rng).with_route_kind(self.route_kind, self.layover);
         // Get the bus which has more stops serviced
         let mut longer = &self.serviced_stop_names;
         let mut shorter = &other.serviced_stop_names;
@@ -112,9 +274,11 @@ where
         Ok(child)
     }
 
-    fn mutate(&mut self) {
+    fn mutate<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        if self.serviced_stop_names.is_empty() {
+            return;
+        }
         // we gonna remove a stop
-        let rng = &mut thread_rng();
         let stop =
             self.serviced_stop_names[rng.gen_range(0..self.serviced_stop_names.len())].clone();
         self.remove_services_stop(stop)
@@ -152,3 +316,68 @@ impl Display for Bus {
         write!(f, "[Bus {}]", self.uuid)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Bus, RouteKind};
+    use crate::rng::sim_rng::seeded_rng;
+
+    fn bus_on(kind: RouteKind) -> Bus {
+        let mut bus = Bus::new(5, &mut seeded_rng(0)).with_route_kind(kind, 3);
+        for stop in ["A", "B", "C"] {
+            bus.add_serviced_stop(stop.to_string());
+        }
+        bus
+    }
+
+    /// The stops the bus calls at, starting with the one it is at.
+    fn walk(bus: &mut Bus, hops: usize) -> String {
+        let mut stops: String = bus.get_current_stop().cloned().unwrap_or_default();
+        for _ in 0..hops {
+            match bus.get_next_stop() {
+                Some(next) => stops.push_str(next),
+                None => break,
+            }
+            bus.advance_to_next_stop();
+        }
+        stops
+    }
+
+    #[test]
+    fn route_kinds_decide_where_buses_go_next() {
+        assert_eq!(walk(&mut bus_on(RouteKind::Once), 6), "ABC");
+        assert_eq!(walk(&mut bus_on(RouteKind::Loop), 6), "ABCABCA");
+        assert_eq!(walk(&mut bus_on(RouteKind::OutAndBack), 6), "ABCBABC");
+        assert_eq!(walk(&mut bus_on(RouteKind::ReturnToDepot), 6), "ABCABCA");
+    }
+
+    #[test]
+    fn trips_end_at_the_terminus() {
+        let mut bus = bus_on(RouteKind::OutAndBack);
+        assert!(!bus.layover_due());
+        assert_eq!(bus.current_trip_stops(), ["B", "C"]);
+        walk(&mut bus, 2);
+        assert_eq!(bus.trips(), 1);
+        assert!(bus.layover_due());
+        assert_eq!(bus.current_trip_stops(), ["B", "A"]);
+        walk(&mut bus, 1);
+        assert!(!bus.layover_due());
+
+        let mut bus = bus_on(RouteKind::ReturnToDepot);
+        walk(&mut bus, 2);
+        // nobody boards for the drive back to the depot
+        assert!(bus.current_trip_stops().is_empty());
+        walk(&mut bus, 1);
+        assert!(bus.layover_due());
+        assert_eq!(bus.current_trip_stops(), ["B", "C"]);
+
+        let mut bus = bus_on(RouteKind::Loop);
+        walk(&mut bus, 2);
+        assert_eq!(bus.current_trip_stops(), ["A", "B"]);
+        assert_eq!(
+            RouteKind::Loop.hops(&bus.serviced_stop_names).len(),
+            3,
+            "the loop drives C->A too"
+        );
+    }
+}
@@ -1,66 +1,87 @@
+use std::any::Any;
+use std::collections::BTreeSet;
 use std::fmt::{Display, Error, Formatter};
-use std::str::FromStr;
 
-use crate::des::des::Scheduler;
+use crate::des::des::{EventHandle, Scheduler};
 use crate::environment::bus_world::bus::Bus;
 use crate::environment::bus_world::bus_scenario_traits::{
-    AdvanceVehicleHandler, NewVehicleHandler, PassengerTransportHandler,
+    AdvanceVehicleHandler, NewVehicleHandler, PassengerDemandHandler, PassengerTransportHandler,
 };
 use crate::environment::bus_world::bus_stop::BusStop;
 use crate::environment::bus_world::bus_world_events::new_bus::NewBusesJson;
 use crate::environment::bus_world::bus_world_events::{load_passengers::*, move_bus_to_stop::*};
 use crate::environment::environment::Environment;
 use crate::event::event::Event;
+use crate::rng::sim_rng::{seeded_rng, SimRng};
+use crate::simulation::sim_error::SimError;
 use crate::statistics::data_point::DataPoint;
 use crate::statistics::stats::Stats;
 
+use super::bus_world_events::bus_event::BusEvent;
 use super::bus_world_events::import_bus::ImportBusesJson;
 use super::bus_world_events::move_bus_to_stop::BusToStopMappingJson;
+use super::bus_world_events::passenger_abandon::{PassengerAbandonEvent, PassengerAbandonJson};
+use super::bus_world_events::passenger_arrival::{PassengerArrivalEvent, PassengerArrivalJson};
 use super::bus_world_events::terminal_event::TerminalEvent;
 use super::bus_world_events::unload_passengers::{UnloadPassengersEvent, UnloadPassengersJson};
+use super::journey_planner::JourneyPlanner;
 use super::passenger::Passenger;
+use super::passenger_arrivals::PassengerGenerator;
+use super::route_network::{RouteNetwork, TravelTime};
+use super::trip_records::TripRecord;
 
-use serde::Serialize;
+use fake::{faker::name::en::Name, Fake};
 
-enum BusEventTypes {
-    TerminalEvent,
-    ImportBus,
-    NewBus,
-    MoveBusToStop,
-    LoadPassengers,
-    UnloadPassengers,
-}
+use serde::{Deserialize, Serialize};
 
-impl FromStr for BusEventTypes {
-    type Err = ();
+/// Time from turning up at a stop to boarding, one observation per boarding.
+pub const WAIT_TIME: &str = "Passenger Wait Time";
+/// Time on the bus, one observation per passenger getting off.
+pub const IN_VEHICLE_TIME: &str = "Passenger In-Vehicle Time";
+/// Time from turning up at a stop to getting off, one observation per
+/// passenger getting off.
+pub const JOURNEY_TIME: &str = "Passenger Journey Time";
+/// Time waiting at a stop between getting off one bus and boarding the
+/// next, one observation per transfer.
+pub const TRANSFER_WAIT: &str = "Passenger Transfer Wait Time";
+/// Changes of bus, one observation per passenger reaching their destination.
+pub const TRANSFERS: &str = "Passenger Transfers";
+/// Passengers still waiting at a stop when the run ends.
+pub const STILL_WAITING: &str = "Passengers Waiting At End";
+/// Passengers still on a bus when the run ends.
+pub const STILL_RIDING: &str = "Passengers Riding At End";
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "Terminal" => Ok(BusEventTypes::TerminalEvent),
-            "ImportBus" => Ok(BusEventTypes::ImportBus),
-            "NewBus" => Ok(BusEventTypes::NewBus),
-            "MoveBusToStop" => Ok(BusEventTypes::MoveBusToStop),
-            "LoadPassengers" => Ok(BusEventTypes::LoadPassengers),
-            "UnloadPassengers" => Ok(BusEventTypes::UnloadPassengers),
-            _ => Err(()),
-        }
-    }
-}
+/// Passengers who balked at a long queue or gave up waiting, one
+/// observation per passenger.
+pub const LOST_DEMAND: &str = "Lost Demand";
 
-#[derive(Serialize, Copy, Clone)]
+#[derive(Deserialize, Serialize, Copy, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
 pub struct BusEnvironmentSettings {
     pickup_delay: usize,
     drop_off_delay: usize,
     next_stop_delay: usize,
     initial_delay: usize,
+    seed: u64,
+    /// Passengers arriving at a stop leave straight away when at least this
+    /// many are already waiting to go where they are going.
+    balk_queue: Option<usize>,
+    /// How long a passenger waits at a stop before giving up.
+    max_wait: Option<TravelTime>,
 }
 
 impl Display for BusEnvironmentSettings {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         write!(
             f,
-            "pickup_delay: {}, drop_off_delay: {}, next_stop_delay: {}, initial_delay: {}",
-            self.pickup_delay, self.drop_off_delay, self.next_stop_delay, self.initial_delay
+            "pickup_delay: {}, drop_off_delay: {}, next_stop_delay: {}, initial_delay: {}, seed: {}, balk_queue: {:?}, max_wait: {:?}",
+            self.pickup_delay,
+            self.drop_off_delay,
+            self.next_stop_delay,
+            self.initial_delay,
+            self.seed,
+            self.balk_queue,
+            self.max_wait
         )
     }
 }
@@ -71,14 +92,59 @@ impl BusEnvironmentSettings {
         drop_off_delay: usize,
         next_stop_delay: usize,
         initial_delay: usize,
+        seed: u64,
     ) -> Self {
         BusEnvironmentSettings {
             pickup_delay,
             drop_off_delay,
             next_stop_delay,
             initial_delay,
+            seed,
+            balk_queue: None,
+            max_wait: None,
+        }
+    }
+
+    /// The same settings with passengers that balk and renege.
+    pub fn with_patience(self, balk_queue: Option<usize>, max_wait: Option<TravelTime>) -> Self {
+        BusEnvironmentSettings {
+            balk_queue,
+            max_wait,
+            ..self
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self.max_wait {
+            Some(max_wait) => max_wait
+                .validate()
+                .map_err(|reason| format!("max_wait: {}", reason)),
+            None => Ok(()),
         }
     }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        BusEnvironmentSettings { seed, ..self }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Change one of the delays, or `balk_queue`, by name, `None` if there
+    /// is no such setting.
+    pub fn with_delay(self, name: &str, value: usize) -> Option<Self> {
+        let mut settings = self;
+        match name {
+            "pickup_delay" => settings.pickup_delay = value,
+            "drop_off_delay" => settings.drop_off_delay = value,
+            "next_stop_delay" => settings.next_stop_delay = value,
+            "initial_delay" => settings.initial_delay = value,
+            "balk_queue" => settings.balk_queue = Some(value),
+            _ => return None,
+        }
+        Some(settings)
+    }
 }
 
 impl Default for BusEnvironmentSettings {
@@ -88,52 +154,200 @@ impl Default for BusEnvironmentSettings {
             drop_off_delay: 2,
             next_stop_delay: 5,
             initial_delay: 10,
+            seed: 0,
+            balk_queue: None,
+            max_wait: None,
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct BusEnvironment {
     pub bus_stops: Vec<BusStop>,
+    /// Links between the stops. Without any, every hop takes `next_stop_delay`.
+    #[serde(default)]
+    network: RouteNetwork,
+    /// The routes buses drive, for passengers to plan transfers with.
+    #[serde(default)]
+    planner: JourneyPlanner,
+    /// Passengers turning up at stops while the simulation runs.
+    #[serde(default)]
+    arrivals: Vec<PassengerGenerator>,
+    /// Passengers created so far, the uid of the next one.
+    #[serde(default)]
+    passengers_created: usize,
     settings: BusEnvironmentSettings,
+    rng: SimRng,
 }
 
 impl BusEnvironment {
     pub fn new(settings: BusEnvironmentSettings) -> BusEnvironment {
         BusEnvironment {
             bus_stops: Vec::new(),
+            network: RouteNetwork::new(),
+            planner: JourneyPlanner::new(),
+            arrivals: Vec::new(),
+            passengers_created: 0,
             settings,
+            rng: seeded_rng(settings.seed),
         }
     }
 
-    pub fn add_bus_to_start(&mut self, bus: Bus) -> Result<(), String> {
+    pub fn add_bus_to_start(&mut self, bus: Bus) -> Result<(), SimError> {
         if let Some(stop) = self.bus_stops.first_mut() {
             stop.add_bus(bus);
             Ok(())
         } else {
-            Err("No bus stops exist".to_string())
+            Err(SimError::InvalidState("No bus stops exist".to_string()))
         }
     }
 
-    pub fn initialize_bus_stops_with_passengers(&mut self, count: usize) {
-        if self.bus_stops.is_empty() {
-            panic!("Error: No bus stops exist");
+    pub fn initialize_bus_stops_with_passengers(&mut self, count: usize) -> Result<(), SimError> {
+        // passengers always travel to a later stop, so we need at least two
+        if self.bus_stops.len() < 2 {
+            return Err(SimError::InvalidState(format!(
+                "Need at least 2 bus stops to generate passengers, found {}",
+                self.bus_stops.len()
+            )));
         }
         let mut bus_stop_names: Vec<String> = Vec::new();
         for stop in self.bus_stops.iter() {
             bus_stop_names.push(stop.name.clone());
         }
-        for i in 0..count {
-            let passenger = Passenger::new_random_passenger(i, &bus_stop_names);
-            // this could be much better, but lets deal with efficiencies later
-            if let Some(initial_stop) = self
-                .bus_stops
-                .iter_mut()
-                .find(|stop| stop.name == passenger.source)
-            {
-                initial_stop.add_passenger(passenger);
+        self.add_random_passengers(&bus_stop_names, count)
+    }
+
+    /// Add passengers travelling between random stops along `route`, always
+    /// towards a later stop on it.
+    pub fn add_random_passengers(
+        &mut self,
+        route: &[String],
+        count: usize,
+    ) -> Result<(), SimError> {
+        if route.len() < 2 {
+            return Err(SimError::InvalidState(format!(
+                "Need at least 2 bus stops to generate passengers, found {}",
+                route.len()
+            )));
+        }
+        for _ in 0..count {
+            let uid = self.next_passenger_uid();
+            let passenger = Passenger::new_random_passenger(uid, route, &mut self.rng);
+            self.find_mut_stop_by_name(&passenger.source)?
+                .add_passenger(passenger);
+        }
+        Ok(())
+    }
+
+    /// Add `count` passengers waiting at `source` to travel to `destination`.
+    pub fn add_passengers(
+        &mut self,
+        source: &str,
+        destination: &str,
+        count: usize,
+    ) -> Result<(), SimError> {
+        self.find_mut_stop_by_name(destination)?;
+        for _ in 0..count {
+            let passenger = self.new_passenger(source, destination, 0);
+            self.find_mut_stop_by_name(source)?.add_passenger(passenger);
+        }
+        Ok(())
+    }
+
+    fn next_passenger_uid(&mut self) -> usize {
+        self.passengers_created += 1;
+        self.passengers_created - 1
+    }
+
+    fn new_passenger(&mut self, source: &str, destination: &str, arrival_time: usize) -> Passenger {
+        let uid = self.next_passenger_uid();
+        let name: String = Name().fake_with_rng(&mut self.rng);
+        // without a plan they wait for a bus straight to their destination
+        let legs = self.planner.plan(source, destination).unwrap_or_default();
+        Passenger::new(uid, name, source.to_string(), destination.to_string())
+            .with_arrival_time(arrival_time)
+            .with_legs(legs)
+    }
+
+    /// Let passengers created from now on plan journeys, changing buses
+    /// between the planner's routes.
+    pub fn set_planner(&mut self, planner: JourneyPlanner) {
+        self.planner = planner;
+    }
+
+    /// Have passengers arrive at a stop while the simulation runs. The
+    /// arrivals start with the events from [BusEnvironment::first_arrivals].
+    pub fn add_passenger_generator(&mut self, generator: PassengerGenerator) {
+        self.arrivals.push(generator);
+    }
+
+    /// The first arrival of every passenger generator, to be scheduled
+    /// before the run. Every arrival schedules the next one.
+    pub fn first_arrivals(&mut self) -> Vec<PassengerArrivalEvent> {
+        let mut events = Vec::new();
+        for (index, generator) in self.arrivals.iter_mut().enumerate() {
+            if let Some(time) = generator.next_arrival(&mut self.rng) {
+                events.push(PassengerArrivalEvent::new(
+                    index,
+                    time,
+                    PassengerArrivalJson::new(index),
+                ));
             }
         }
+        events
+    }
+
+    /// Every passenger in the world: waiting, riding, arrived or lost.
+    pub fn passengers(&self) -> Vec<&Passenger> {
+        self.bus_stops
+            .iter()
+            .flat_map(|stop| {
+                stop.waiting_passengers
+                    .values()
+                    .flatten()
+                    .chain(&stop.completed_passengers)
+                    .chain(&stop.lost_passengers)
+                    .chain(
+                        stop.buses_at_stop
+                            .iter()
+                            .flat_map(|bus| bus.passengers.values().flatten()),
+                    )
+            })
+            .collect()
+    }
+
+    /// How many passengers there are in the world, see [BusEnvironment::passengers].
+    pub fn passenger_count(&self) -> usize {
+        self.passengers().len()
+    }
+
+    /// A new, empty bus with an id drawn from the environment's random number generator.
+    pub fn new_bus(&mut self, capacity: usize) -> Bus {
+        Bus::new(capacity, &mut self.rng)
+    }
+
+    pub fn network(&self) -> &RouteNetwork {
+        &self.network
+    }
+
+    pub fn set_network(&mut self, network: RouteNetwork) {
+        self.network = network;
+    }
+
+    /// How long the hop from `from` to `to` takes this time, and how far it is.
+    fn sample_hop(&mut self, from: &str, to: &str) -> Result<(usize, Option<f64>), SimError> {
+        if self.network.is_empty() {
+            return Ok((self.settings.next_stop_delay, None));
+        }
+        let link = self.network.link(from, to)?;
+        let distance = link.distance;
+        let travel_time = link.travel_time;
+        Ok((travel_time.sample(&mut self.rng), Some(distance)))
+    }
+
+    /// Add a stop with the given name.
+    pub fn add_bus_stop(&mut self, name: String) {
+        self.bus_stops.push(BusStop::new(name));
     }
 
     pub fn create_bus_stops(&mut self, count: usize) {
@@ -144,79 +358,98 @@ impl BusEnvironment {
         }
     }
 
-    fn find_mut_stop_by_name(&mut self, stop_name: &str) -> Option<&mut BusStop> {
+    fn find_mut_stop_by_name(&mut self, stop_name: &str) -> Result<&mut BusStop, SimError> {
         self.bus_stops
             .iter_mut()
             .find(|stop| stop.name == stop_name)
+            .ok_or(SimError::missing("Stop", stop_name))
     }
 
-    fn find_mut_stop_by_bus_uuid(&mut self, uuid: String) -> Option<&mut BusStop> {
-        for stop in self.bus_stops.iter_mut() {
-            let bus = stop.buses_at_stop.iter_mut().find(|bus| bus.uuid == uuid);
-            if bus.is_some() {
-                return Some(stop);
-            }
-        }
-        return None;
+    fn find_mut_stop_by_bus_uuid(&mut self, uuid: &str) -> Result<&mut BusStop, SimError> {
+        self.bus_stops
+            .iter_mut()
+            .find(|stop| stop.buses_at_stop.iter().any(|bus| bus.uuid == uuid))
+            .ok_or(SimError::missing("Bus", uuid))
     }
 
-    fn drain_bus_by_uuid(&mut self, bus_uuid: String) -> Option<Bus> {
-        for stop in &mut self.bus_stops {
-            if stop
-                .buses_at_stop
-                .iter()
-                .filter(|b| b.uuid == bus_uuid)
-                .count()
-                > 0
-            {
-                return Some(stop.drain_bus(bus_uuid));
+    /// When the passengers waiting before the run starts give up, if they
+    /// have a limited patience, to be scheduled before the run.
+    pub fn initial_abandonments(&mut self) -> Vec<PassengerAbandonEvent> {
+        let Some(max_wait) = self.settings.max_wait else {
+            return Vec::new();
+        };
+        let mut events = Vec::new();
+        for stop in &self.bus_stops {
+            for passenger in stop.waiting_passengers.values().flatten() {
+                events.push(PassengerAbandonEvent::new(
+                    passenger.uid,
+                    passenger.arrival_time + max_wait.sample(&mut self.rng),
+                    PassengerAbandonJson::new(stop.name.clone(), passenger.uid),
+                ));
             }
         }
-        return None;
+        events
     }
 
-    pub fn record_total_wait_time(&self, timestamp: usize, stat_recorder: &mut Stats) {
-        let mut total_wait_time: usize = self.bus_stops.iter().fold(0, |acc, stop| {
-            acc + stop
-                .completed_passengers
-                .iter()
-                .fold(0, |acc, passenger| acc + passenger.wait_time as usize)
-        });
-
-        for stop in &self.bus_stops {
-            for bus in &stop.buses_at_stop {
-                total_wait_time += bus.passengers.values().fold(0, |acc, passengers| {
-                    acc + passengers
-                        .iter()
-                        .fold(0, |acc, passenger| acc + passenger.wait_time as usize)
-                });
-            }
-        }
+    /// Remember the event in which a waiting passenger gives up, so that
+    /// it is cancelled if they board first.
+    pub fn set_abandon_event(
+        &mut self,
+        stop_name: &str,
+        passenger_uid: usize,
+        handle: EventHandle,
+    ) -> Result<(), SimError> {
+        let passenger = self
+            .find_mut_stop_by_name(stop_name)?
+            .waiting_passengers
+            .values_mut()
+            .flatten()
+            .find(|passenger| passenger.uid == passenger_uid)
+            .ok_or(SimError::missing("Passenger", passenger_uid.to_string()))?;
+        passenger.abandon_event = Some(handle);
+        Ok(())
+    }
 
-        stat_recorder.add_statistic(
-            DataPoint::new(timestamp, total_wait_time as f64, "ms".to_string()),
-            "Total Passenger Wait Time".to_string(),
-        );
+    /// One record per passenger who reached their destination, by uid.
+    pub fn trip_records(&self) -> Vec<TripRecord> {
+        let mut records: Vec<TripRecord> = self
+            .bus_stops
+            .iter()
+            .flat_map(|stop| &stop.completed_passengers)
+            .filter_map(TripRecord::from_passenger)
+            .collect();
+        records.sort_by_key(|record| record.uid);
+        records
     }
 
-    fn terminate_bus_sim(&mut self, stat_recorder: &mut Stats, event: Box<dyn Event>) {
+    /// Report how many passengers are still waiting or riding when the run
+    /// ends. Their journeys are missing from the time distributions.
+    fn terminate_bus_sim(&mut self, stat_recorder: &mut Stats, event: &dyn Event<BusEvent>) {
         let timestamp = event.get_time_stamp();
-        for stop in &mut self.bus_stops {
-            for bus in &mut stop.buses_at_stop {
-                for bus_load in bus.passengers.values_mut() {
-                    for passenger in bus_load.iter_mut() {
-                        passenger.wait_time += timestamp as u32;
-                    }
-                }
-            }
-        }
-        // empty the buses
-        for stop in &mut self.bus_stops {
-            for bus in &mut stop.buses_at_stop {
-                bus.reset();
-            }
-        }
-        self.record_total_wait_time(timestamp, stat_recorder);
+        let waiting: usize = self
+            .bus_stops
+            .iter()
+            .map(|stop| {
+                stop.waiting_passengers
+                    .values()
+                    .map(Vec::len)
+                    .sum::<usize>()
+            })
+            .sum();
+        let riding: usize = self
+            .bus_stops
+            .iter()
+            .flat_map(|stop| &stop.buses_at_stop)
+            .map(Bus::current_passenger_count)
+            .sum();
+        stat_recorder.add_statistic(
+            DataPoint::new(timestamp, waiting as f64, "passengers".to_string()),
+            STILL_WAITING.to_string(),
+        );
+        stat_recorder.add_statistic(
+            DataPoint::new(timestamp, riding as f64, "passengers".to_string()),
+            STILL_RIDING.to_string(),
+        );
     }
 }
 
@@ -227,33 +460,35 @@ impl Default for BusEnvironment {
 }
 
 impl Environment for BusEnvironment {
+    type Payload = BusEvent;
+
     fn apply_event(
         &mut self,
-        scheduler: &mut Scheduler,
+        scheduler: &mut Scheduler<BusEvent>,
         stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    ) {
-        match BusEventTypes::from_str(event.get_event_type()) {
-            Ok(BusEventTypes::TerminalEvent) => {
+        event: &dyn Event<BusEvent>,
+    ) -> Result<(), SimError> {
+        match event.get_payload() {
+            BusEvent::Terminal => {
                 self.terminate_bus_sim(stat_recorder, event);
+                Ok(())
             }
-            Ok(BusEventTypes::ImportBus) => {
-                self.import_buses(scheduler, stat_recorder, event);
-            }
-            Ok(BusEventTypes::NewBus) => {
-                self.create_new_bus(scheduler, stat_recorder, event);
+            BusEvent::ImportBus(data) => self.import_buses(scheduler, stat_recorder, event, data),
+            BusEvent::NewBus(data) => self.create_new_bus(scheduler, stat_recorder, event, data),
+            BusEvent::MoveBusToStop(data) => {
+                self.advance_bus_to_next_stop(scheduler, stat_recorder, event, data)
             }
-            Ok(BusEventTypes::MoveBusToStop) => {
-                self.advance_bus_to_next_stop(scheduler, stat_recorder, event);
+            BusEvent::LoadPassengers(data) => {
+                self.load_passengers(scheduler, stat_recorder, event, data)
             }
-            Ok(BusEventTypes::LoadPassengers) => {
-                self.load_passengers(scheduler, stat_recorder, event);
+            BusEvent::UnloadPassengers(data) => {
+                self.unload_passengers(scheduler, stat_recorder, event, data)
             }
-            Ok(BusEventTypes::UnloadPassengers) => {
-                self.unload_passengers(scheduler, stat_recorder, event);
+            BusEvent::PassengerArrival(data) => {
+                self.passenger_arrival(scheduler, stat_recorder, event, data)
             }
-            Err(()) => {
-                panic!("Error: Unknown event type {}", event.get_event_type())
+            BusEvent::PassengerAbandon(data) => {
+                self.passenger_abandon(scheduler, stat_recorder, event, data)
             }
         }
     }
@@ -263,55 +498,138 @@ impl Environment for BusEnvironment {
         //     number_of_buses += bus_stop.buses_at_stop.len();
         // }
         // format!("Number of buses: {}", number_of_buses)
-        if let Ok(serialized) = serde_json::to_string(&self.bus_stops) {
+        if let Ok(serialized) = serde_json::to_string(&self) {
             return serialized;
         }
-        return String::new();
+        String::new()
+    }
+
+    fn restore_state(&mut self, state: &str) -> Result<(), SimError> {
+        *self = serde_json::from_str(state)?;
+        Ok(())
+    }
+
+    fn terminating_event(&self) -> Box<dyn Event<BusEvent>> {
+        Box::new(TerminalEvent::new(0, 0))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
     }
 
-    fn terminating_event(&self) -> Box<dyn Event> {
-        Box::new(TerminalEvent::new(0, 0, String::new()))
+    /// One line per stop: passengers waiting and arrived, and every bus
+    /// at the stop with its load and next stop.
+    fn status_lines(&self) -> Vec<String> {
+        self.bus_stops
+            .iter()
+            .map(|stop| {
+                let waiting: usize = stop.waiting_passengers.values().map(Vec::len).sum();
+                let buses: Vec<String> = stop
+                    .buses_at_stop
+                    .iter()
+                    .map(|bus| {
+                        format!(
+                            "{} ({}/{} -> {})",
+                            bus.uuid.chars().take(8).collect::<String>(),
+                            bus.current_passenger_count(),
+                            bus.capacity,
+                            bus.get_next_stop().map_or("end", String::as_str)
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{:<6} waiting {:>4}  arrived {:>4}  lost {:>4}  buses: {}",
+                    stop.name,
+                    waiting,
+                    stop.completed_passengers.len(),
+                    stop.lost_passengers.len(),
+                    buses.join(", ")
+                )
+            })
+            .collect()
     }
 }
 
 impl PassengerTransportHandler for BusEnvironment {
     fn load_passengers(
         &mut self,
-        scheduler: &mut Scheduler,
+        scheduler: &mut Scheduler<BusEvent>,
         stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    ) {
-        let bus_uuid = serde_json::from_str::<LoadPassengersJson>(&event.get_data().unwrap())
-            .expect("Error: Could not deserialize bus mapping")
-            .bus_uuid;
+        event: &dyn Event<BusEvent>,
+        data: &LoadPassengersJson,
+    ) -> Result<(), SimError> {
+        let bus_uuid = data.bus_uuid.clone();
         let passenger_pickup_delay = self.settings.pickup_delay;
-        let stop = self.find_mut_stop_by_bus_uuid(bus_uuid.clone()).unwrap();
+        let stop = self.find_mut_stop_by_bus_uuid(&bus_uuid)?;
         let bus_at_stop = stop
             .buses_at_stop
             .iter_mut()
             .find(|b| b.uuid == bus_uuid)
-            .unwrap(); // unwrap bad.
+            .ok_or(SimError::missing("Bus", bus_uuid.as_str()))?;
 
+        // only passengers going somewhere the bus still calls at on this trip board
+        let trip_stops: Vec<String> = bus_at_stop
+            .current_trip_stops()
+            .into_iter()
+            .cloned()
+            .collect();
         let mut onboarded_passengers_count = 0;
-        for key in &bus_at_stop.serviced_stop_names.clone() {
+        for key in &trip_stops {
             if let Some(tentative_onboarders) = stop.waiting_passengers.get_mut(key) {
-                while !tentative_onboarders.is_empty()
-                    && bus_at_stop.current_passenger_count() < bus_at_stop.capacity
-                {
-                    bus_at_stop.add_passenger(tentative_onboarders.pop().unwrap());
+                // first come, first served
+                // imported buses may already carry more than their capacity
+                let space = bus_at_stop
+                    .capacity
+                    .saturating_sub(bus_at_stop.current_passenger_count());
+                let boarding = space.min(tentative_onboarders.len());
+                for mut passenger in tentative_onboarders.drain(..boarding) {
+                    // they no longer give up waiting
+                    if let Some(handle) = passenger.abandon_event.take() {
+                        scheduler.cancel(handle);
+                    }
+                    let (label, wait_time) =
+                        match passenger.board(event.get_time_stamp(), &bus_uuid) {
+                            Some(transfer_wait) => (TRANSFER_WAIT, Some(transfer_wait)),
+                            None => (WAIT_TIME, passenger.wait_time()),
+                        };
+                    if let Some(wait_time) = wait_time {
+                        stat_recorder.add_statistic(
+                            DataPoint::new(
+                                event.get_time_stamp(),
+                                wait_time as f64,
+                                "time".to_string(),
+                            ),
+                            label.to_string(),
+                        );
+                    }
+                    bus_at_stop.add_passenger(passenger);
                     onboarded_passengers_count += 1;
-                    // unwrap bad
+                }
+                // anyone still waiting for this bus did not fit
+                for passenger in tentative_onboarders.iter_mut() {
+                    passenger.times_left_behind += 1;
                 }
             }
         }
 
-        // Schedule advance to next stop if exists
+        // Schedule advance to next stop if exists, after the layover if a new trip starts here
         if let Some(next_stop) = bus_at_stop.get_next_stop() {
+            let layover = if bus_at_stop.layover_due() {
+                bus_at_stop.layover
+            } else {
+                0
+            };
             let advance_to_next_stop_data = BusToStopMappingJson::new(bus_uuid, next_stop.clone());
             let advance_to_next_stop_event = Box::new(MoveBusToStopEvent::new(
                 event.get_uid() + 1,
-                event.get_time_stamp() + (onboarded_passengers_count * passenger_pickup_delay),
-                serde_json::to_string(&advance_to_next_stop_data).unwrap(),
+                event.get_time_stamp()
+                    + (onboarded_passengers_count * passenger_pickup_delay)
+                    + layover,
+                advance_to_next_stop_data,
             ));
             scheduler.add_event(advance_to_next_stop_event);
         }
@@ -326,70 +644,218 @@ impl PassengerTransportHandler for BusEnvironment {
             data_point,
             format!("Bus {}: Passengers Loaded", bus_at_stop.uuid),
         );
+        Ok(())
     }
 
     fn unload_passengers(
         &mut self,
-        scheduler: &mut Scheduler,
+        scheduler: &mut Scheduler<BusEvent>,
         stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    ) {
-        let bus_uuid = serde_json::from_str::<LoadPassengersJson>(&event.get_data().unwrap())
-            .expect("Error: Could not deserialize bus mapping")
-            .bus_uuid;
+        event: &dyn Event<BusEvent>,
+        data: &UnloadPassengersJson,
+    ) -> Result<(), SimError> {
+        let bus_uuid = data.bus_uuid.clone();
+        let passenger_drop_off_delay = self.settings.drop_off_delay;
         let mut unloaded_passenger_count = 0;
-        if let Some(stop) = self.find_mut_stop_by_bus_uuid(bus_uuid.clone()) {
-            let bus_at_stop = stop
-                .buses_at_stop
-                .iter_mut()
-                .find(|b| b.uuid == bus_uuid)
-                .unwrap(); // unwrap bad.
-            if let Some(passengers_getting_off) = bus_at_stop.passengers.get_mut(stop.name.as_str())
-            {
-                unloaded_passenger_count = passengers_getting_off.len();
-                for p in passengers_getting_off.iter_mut() {
-                    p.wait_time += event.get_time_stamp() as u32;
+        let mut transferring = Vec::new();
+        let stop = self.find_mut_stop_by_bus_uuid(&bus_uuid)?;
+        let bus_at_stop = stop
+            .buses_at_stop
+            .iter_mut()
+            .find(|b| b.uuid == bus_uuid)
+            .ok_or(SimError::missing("Bus", bus_uuid.as_str()))?;
+        if let Some(passengers_getting_off) = bus_at_stop.passengers.remove(stop.name.as_str()) {
+            unloaded_passenger_count = passengers_getting_off.len();
+            for mut p in passengers_getting_off {
+                p.alight(event.get_time_stamp(), &stop.name);
+                // changing buses here, so wait for the next leg
+                if !p.at_destination() {
+                    p.transfer();
+                    transferring.push(p);
+                    continue;
                 }
-                stop.completed_passengers.append(passengers_getting_off);
+                for (label, unit, value) in [
+                    (IN_VEHICLE_TIME, "time", p.in_vehicle_time()),
+                    (JOURNEY_TIME, "time", p.journey_time()),
+                    (TRANSFERS, "transfers", Some(p.transfers)),
+                ] {
+                    if let Some(value) = value {
+                        stat_recorder.add_statistic(
+                            DataPoint::new(event.get_time_stamp(), value as f64, unit.to_string()),
+                            label.to_string(),
+                        );
+                    }
+                }
+                stop.completed_passengers.push(p);
             }
+        }
 
-            // Schedule loading passengers after we unloaded passengers
-            let load_passengers_data = LoadPassengersJson::new(bus_uuid);
-            let load_bus_event = Box::new(LoadPassengersEvent::new(
+        // Schedule loading passengers after we unloaded passengers
+        let load_passengers_data = LoadPassengersJson::new(bus_uuid);
+        let load_bus_event = Box::new(LoadPassengersEvent::new(
+            event.get_uid() + 1,
+            event.get_time_stamp() + (unloaded_passenger_count * passenger_drop_off_delay),
+            load_passengers_data,
+        ));
+        scheduler.add_event(load_bus_event);
+
+        // Report stats on how many passengers were unloaded
+        let data_point = DataPoint::new(
+            event.get_time_stamp(),
+            unloaded_passenger_count as f64,
+            String::from("Passengers (ct)"),
+        );
+        stat_recorder.add_statistic(
+            data_point,
+            format!("Bus {}: Passengers Unloaded", bus_at_stop.uuid),
+        );
+
+        // Passengers changing buses queue up for their next leg
+        for passenger in transferring {
+            stop.add_passenger(passenger);
+        }
+        Ok(())
+    }
+}
+
+impl PassengerDemandHandler for BusEnvironment {
+    fn passenger_arrival(
+        &mut self,
+        scheduler: &mut Scheduler<BusEvent>,
+        stat_recorder: &mut Stats,
+        event: &dyn Event<BusEvent>,
+        data: &PassengerArrivalJson,
+    ) -> Result<(), SimError> {
+        let generator = self
+            .arrivals
+            .get_mut(data.generator)
+            .ok_or(SimError::missing(
+                "PassengerGenerator",
+                data.generator.to_string(),
+            ))?;
+        let source = generator.stop.clone();
+        let destination = generator
+            .destination(&mut self.rng)
+            .cloned()
+            .ok_or_else(|| {
+                SimError::InvalidState(format!(
+                    "passengers arriving at {} have nowhere to go",
+                    source
+                ))
+            })?;
+
+        // Schedule the next passenger from the same generator
+        if let Some(time) = generator.next_arrival(&mut self.rng) {
+            scheduler.add_event(Box::new(PassengerArrivalEvent::new(
                 event.get_uid() + 1,
-                event.get_time_stamp() + (unloaded_passenger_count * 4),
-                serde_json::to_string(&load_passengers_data).unwrap(),
-            ));
-            scheduler.add_event(load_bus_event);
+                time.max(event.get_time_stamp()),
+                data.clone(),
+            )));
+        }
+
+        let passenger = self.new_passenger(&source, &destination, event.get_time_stamp());
+        let uid = passenger.uid;
+        let first_leg = passenger.leg_destination().clone();
+        let balk_queue = self.settings.balk_queue;
+        let give_up_after = self.settings.max_wait.map(|max| max.sample(&mut self.rng));
+        let stop = self.find_mut_stop_by_name(&source)?;
 
-            // Report stats on how many passengers were unloaded
-            let data_point = DataPoint::new(
+        // Stats, one more passenger arrived here
+        let data_point = DataPoint::new(event.get_time_stamp(), 1.0, "passengers".to_string());
+        stat_recorder.add_statistic(data_point, format!("stop {}: arrivals", stop.name));
+
+        // The queue is too long, so they do not even join it
+        if balk_queue.is_some_and(|limit| stop.queue_length(&first_leg) >= limit) {
+            stop.lost_passengers.push(passenger);
+            record_lost_passenger(stat_recorder, event.get_time_stamp(), &source, "balked");
+            return Ok(());
+        }
+
+        // Schedule giving up if no bus picks them up in time
+        let mut passenger = passenger;
+        if let Some(wait) = give_up_after {
+            passenger.abandon_event =
+                Some(scheduler.add_event(Box::new(PassengerAbandonEvent::new(
+                    event.get_uid() + 2,
+                    event.get_time_stamp() + wait,
+                    PassengerAbandonJson::new(source, uid),
+                ))));
+        }
+        stop.add_passenger(passenger);
+        Ok(())
+    }
+
+    fn passenger_abandon(
+        &mut self,
+        _scheduler: &mut Scheduler<BusEvent>,
+        stat_recorder: &mut Stats,
+        event: &dyn Event<BusEvent>,
+        data: &PassengerAbandonJson,
+    ) -> Result<(), SimError> {
+        let stop = self.find_mut_stop_by_name(&data.stop_name)?;
+        // passengers who already boarded have nothing to give up on
+        if let Some(passenger) = stop.remove_waiting_passenger(data.passenger_uid) {
+            stop.lost_passengers.push(passenger);
+            record_lost_passenger(
+                stat_recorder,
                 event.get_time_stamp(),
-                unloaded_passenger_count as f64,
-                String::from("Passengers (ct)"),
-            );
-            stat_recorder.add_statistic(
-                data_point,
-                format!("Bus {}: Passengers Unloaded", bus_at_stop.uuid),
+                &data.stop_name,
+                "reneged",
             );
         }
+        Ok(())
     }
 }
 
+/// Count a passenger in the lost demand and in the stop's `reason` series.
+fn record_lost_passenger(stat_recorder: &mut Stats, timestamp: usize, stop: &str, reason: &str) {
+    stat_recorder.add_statistic(
+        DataPoint::new(timestamp, 1.0, "passengers".to_string()),
+        LOST_DEMAND.to_string(),
+    );
+    stat_recorder.add_statistic(
+        DataPoint::new(timestamp, 1.0, "passengers".to_string()),
+        format!("stop {}: {}", stop, reason),
+    );
+}
+
 impl AdvanceVehicleHandler for BusEnvironment {
     fn advance_bus_to_next_stop(
         &mut self,
-        scheduler: &mut Scheduler,
+        scheduler: &mut Scheduler<BusEvent>,
         stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    ) {
-        // // This should be handled and not unwrapped, but whatever
-        let bus_and_new_stop =
-            serde_json::from_str::<BusToStopMappingJson>(&event.get_data().unwrap()).unwrap();
+        event: &dyn Event<BusEvent>,
+        bus_and_new_stop: &BusToStopMappingJson,
+    ) -> Result<(), SimError> {
+        // make sure the destination exists and is linked to the current stop
+        // before taking the bus off its current stop
+        self.find_mut_stop_by_name(&bus_and_new_stop.stop_name)?;
+        let departed_stop_name = self
+            .find_mut_stop_by_bus_uuid(&bus_and_new_stop.bus_uuid)?
+            .name
+            .clone();
+        let (travel_time, distance) =
+            self.sample_hop(&departed_stop_name, &bus_and_new_stop.stop_name)?;
 
         // find and drain the bus we are looking for and do something with it later
-        // unwrapping is bad
-        let mut bus = self.drain_bus_by_uuid(bus_and_new_stop.bus_uuid).unwrap();
+        let departed_stop = self.find_mut_stop_by_bus_uuid(&bus_and_new_stop.bus_uuid)?;
+        let mut bus = departed_stop
+            .drain_bus(&bus_and_new_stop.bus_uuid)
+            .ok_or(SimError::missing("Bus", &bus_and_new_stop.bus_uuid))?;
+
+        // Stats, the stop we left has one bus fewer
+        let data_point = DataPoint::new(
+            event.get_time_stamp(),
+            departed_stop.buses_at_stop.len() as f64,
+            "bus_count".to_string(),
+        );
+        stat_recorder.add_level(data_point, format!("stop {}: buses", departed_stop.name));
+
+        if let Some(distance) = distance {
+            let data_point =
+                DataPoint::new(event.get_time_stamp(), distance, "distance".to_string());
+            stat_recorder.add_statistic(data_point, format!("Bus {}: Distance", bus.uuid));
+        }
 
         // Advance the bus to the current stop(advanced by 1 stop)
         bus.advance_to_next_stop();
@@ -398,17 +864,13 @@ impl AdvanceVehicleHandler for BusEnvironment {
         let unload_passengers_data = UnloadPassengersJson::new(bus.uuid.clone());
         let unload_passengers_event = Box::new(UnloadPassengersEvent::new(
             event.get_uid() + 1,
-            event.get_time_stamp() + self.settings.next_stop_delay,
-            serde_json::to_string(&unload_passengers_data).unwrap(),
+            event.get_time_stamp() + travel_time,
+            unload_passengers_data,
         ));
         scheduler.add_event(unload_passengers_event);
 
         // find the stop we are looking for and add the bus to it
-        // unwrapping is bad
-        let stop = match self.find_mut_stop_by_name(&bus_and_new_stop.stop_name) {
-            Some(stop) => stop,
-            None => panic!("Error: Stop {} not found", bus_and_new_stop.stop_name),
-        };
+        let stop = self.find_mut_stop_by_name(&bus_and_new_stop.stop_name)?;
 
         // Finally, add the bus to the current stop.
         stop.add_bus(bus);
@@ -419,81 +881,89 @@ impl AdvanceVehicleHandler for BusEnvironment {
             stop.buses_at_stop.len() as f64,
             "bus_count".to_string(),
         );
-        stat_recorder.add_statistic(data_point, format!("stop {}: buses", stop.name));
+        stat_recorder.add_level(data_point, format!("stop {}: buses", stop.name));
+        Ok(())
     }
 }
 
 impl NewVehicleHandler for BusEnvironment {
     fn create_new_bus(
         &mut self,
-        scheduler: &mut Scheduler,
+        scheduler: &mut Scheduler<BusEvent>,
         _stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    ) {
-        let bus_mapping = serde_json::from_str::<NewBusesJson>(&event.get_data().unwrap())
-            .expect("Error: Could not deserialize bus mapping");
-
+        event: &dyn Event<BusEvent>,
+        bus_mapping: &NewBusesJson,
+    ) -> Result<(), SimError> {
+        if self.bus_stops.is_empty() {
+            return Err(SimError::InvalidState(
+                "Cannot create buses without bus stops".to_string(),
+            ));
+        }
         for _ in 0..bus_mapping.number_of_buses {
-            let mut bus = Bus::new(bus_mapping.capacity);
+            let mut bus = Bus::new(bus_mapping.capacity, &mut self.rng);
 
             for stop in &mut self.bus_stops {
                 bus.add_serviced_stop(stop.name.clone());
             }
 
-            let _bus_routing = match bus.get_next_stop() {
-                Some(next_stop) => {
-                    BusToStopMappingJson::new(bus.uuid.clone(), next_stop.to_string())
-                }
-                None => BusToStopMappingJson::new(
-                    bus.uuid.clone(),
-                    bus.get_current_stop().unwrap().to_string(),
-                ),
-            };
-
             // Start the Unload -> Load -> Advance Bus cycle
             let schedule_load_passengers = Box::new(UnloadPassengersEvent::new(
                 event.get_uid() + 1,
                 event.get_time_stamp() + self.settings.initial_delay,
-                serde_json::to_string(&UnloadPassengersJson::new(bus.uuid.clone())).unwrap(),
+                UnloadPassengersJson::new(bus.uuid.clone()),
             ));
             scheduler.add_event(schedule_load_passengers);
 
             // Add bus to the stop
             self.bus_stops[0].add_bus(bus);
         }
+        Ok(())
     }
 
     fn import_buses(
         &mut self,
-        scheduler: &mut Scheduler,
-        _stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    ) {
-        let imported_buses = serde_json::from_str::<ImportBusesJson>(&event.get_data().unwrap())
-            .expect("Error: Could not deserialize imported buses");
-
-        for bus in imported_buses.buses {
-            let _bus_routing = match bus.get_next_stop() {
-                Some(next_stop) => {
-                    BusToStopMappingJson::new(bus.uuid.clone(), next_stop.to_string())
-                }
-                None => BusToStopMappingJson::new(
-                    bus.uuid.clone(),
-                    bus.get_current_stop().unwrap().to_string(),
-                ),
-            };
-
+        scheduler: &mut Scheduler<BusEvent>,
+        stat_recorder: &mut Stats,
+        event: &dyn Event<BusEvent>,
+        imported_buses: &ImportBusesJson,
+    ) -> Result<(), SimError> {
+        if self.bus_stops.is_empty() {
+            return Err(SimError::InvalidState(
+                "Cannot import buses without bus stops".to_string(),
+            ));
+        }
+        let mut stops_with_new_buses = BTreeSet::new();
+        // the event keeps its payload, e.g. for a checkpoint, so the world gets copies
+        for bus in imported_buses.buses.iter().cloned() {
             // Start the Unload -> Load -> Advance Bus cycle
             let schedule_load_passengers = Box::new(UnloadPassengersEvent::new(
                 event.get_uid() + 1,
                 event.get_time_stamp() + self.settings.initial_delay,
-                serde_json::to_string(&UnloadPassengersJson::new(bus.uuid.clone())).unwrap(),
+                UnloadPassengersJson::new(bus.uuid.clone()),
             ));
             scheduler.add_event(schedule_load_passengers);
 
-            // Add bus to the stop
-            self.bus_stops[0].add_bus(bus);
+            // Add bus to the first stop on its route
+            let stop = match bus.serviced_stop_names.first().cloned() {
+                Some(first_stop) => self.find_mut_stop_by_name(&first_stop)?,
+                None => &mut self.bus_stops[0],
+            };
+            stop.add_bus(bus);
+            stops_with_new_buses.insert(stop.name.clone());
+        }
+
+        // Stats, report the count of buses at every stop that got some
+        for stop in &self.bus_stops {
+            if stops_with_new_buses.contains(&stop.name) {
+                let data_point = DataPoint::new(
+                    event.get_time_stamp(),
+                    stop.buses_at_stop.len() as f64,
+                    "bus_count".to_string(),
+                );
+                stat_recorder.add_level(data_point, format!("stop {}: buses", stop.name));
+            }
         }
+        Ok(())
     }
 }
 
@@ -516,7 +986,18 @@ mod tests {
     use super::BusEnvironment;
     use crate::des::des::Scheduler;
     use crate::environment::bus_world::bus_environment::BusEnvironmentSettings;
+    use crate::environment::bus_world::bus_world_events::import_bus::{
+        ImportBusEvent, ImportBusesJson,
+    };
+    use crate::environment::bus_world::bus_world_events::load_passengers::{
+        LoadPassengersEvent, LoadPassengersJson,
+    };
+    use crate::environment::bus_world::bus_world_events::move_bus_to_stop::{
+        BusToStopMappingJson, MoveBusToStopEvent,
+    };
     use crate::environment::bus_world::bus_world_events::new_bus::NewBusesJson;
+    use crate::environment::bus_world::passenger::Passenger;
+    use crate::simulation::sim_error::SimError;
     use crate::{
         environment::bus_world::bus_world_events::new_bus::NewBusEvent,
         environment::environment::Environment, statistics::stats::Stats,
@@ -530,13 +1011,110 @@ mod tests {
         assert_eq!(bus_world.bus_stops.len(), 0);
         bus_world.create_bus_stops(1);
         let number_of_buses = NewBusesJson::new(1, 5);
-        let event = Box::new(NewBusEvent::new(
+        let event = NewBusEvent::new(1, 0, number_of_buses);
+        bus_world
+            .apply_event(&mut scheduler, &mut stats_recorder, &event)
+            .unwrap();
+        assert_eq!(bus_world.bus_stops.len(), 1);
+        assert_eq!(bus_world.bus_stops[0].buses_at_stop.len(), 1);
+    }
+
+    #[test]
+    fn missing_bus_is_an_error() {
+        let mut bus_world = BusEnvironment::new(BusEnvironmentSettings::default());
+        let mut scheduler = Scheduler::new(100);
+        let mut stats_recorder = Stats::new();
+        bus_world.create_bus_stops(2);
+        let event = MoveBusToStopEvent::new(
             1,
             0,
-            serde_json::to_string(&number_of_buses).unwrap(),
+            BusToStopMappingJson::new("no-such-bus".to_string(), "B".to_string()),
+        );
+        let result = bus_world.apply_event(&mut scheduler, &mut stats_recorder, &event);
+        assert!(matches!(
+            result,
+            Err(SimError::MissingEntity { kind: "Bus", .. })
+        ));
+    }
+
+    #[test]
+    fn over_full_imported_buses_take_nobody() {
+        let mut bus_world = BusEnvironment::new(BusEnvironmentSettings::default());
+        let mut scheduler = Scheduler::new(100);
+        let mut stats_recorder = Stats::new();
+        bus_world.create_bus_stops(2);
+        bus_world.add_passengers("A", "B", 3).unwrap();
+        let mut bus = bus_world.new_bus(1);
+        bus.add_serviced_stop("A".to_string());
+        bus.add_serviced_stop("B".to_string());
+        for uid in 10..12 {
+            bus.add_passenger(Passenger::new(
+                uid,
+                "Ann".to_string(),
+                "A".to_string(),
+                "B".to_string(),
+            ));
+        }
+        let uuid = bus.uuid.clone();
+        let import = ImportBusEvent::new(1, 0, ImportBusesJson::new(vec![bus]));
+        bus_world
+            .apply_event(&mut scheduler, &mut stats_recorder, &import)
+            .unwrap();
+        let buses_at_a: Vec<(usize, f64)> = stats_recorder
+            .get_series_by_name("stop A: buses".to_string())
+            .unwrap()
+            .observations()
+            .collect();
+        assert_eq!(buses_at_a, vec![(0, 1.0)]);
+
+        let load = LoadPassengersEvent::new(2, 0, LoadPassengersJson::new(uuid));
+        bus_world
+            .apply_event(&mut scheduler, &mut stats_recorder, &load)
+            .unwrap();
+        let stop = &bus_world.bus_stops[0];
+        assert_eq!(stop.queue_length("B"), 3);
+        assert_eq!(stop.buses_at_stop[0].current_passenger_count(), 2);
+    }
+
+    #[test]
+    fn unloading_takes_the_drop_off_delay() {
+        let mut bus_world = BusEnvironment::new(BusEnvironmentSettings::new(1, 3, 5, 10, 0));
+        let mut scheduler = Scheduler::new(100);
+        let mut stats_recorder = Stats::new();
+        bus_world.create_bus_stops(2);
+        let mut bus = bus_world.new_bus(5);
+        bus.add_serviced_stop("A".to_string());
+        bus.add_serviced_stop("B".to_string());
+        for uid in 0..2 {
+            bus.add_passenger(Passenger::new(
+                uid,
+                "Ann".to_string(),
+                "B".to_string(),
+                "A".to_string(),
+            ));
+        }
+        let import = ImportBusEvent::new(1, 0, ImportBusesJson::new(vec![bus]));
+        bus_world
+            .apply_event(&mut scheduler, &mut stats_recorder, &import)
+            .unwrap();
+
+        // the bus unloads after the initial delay, then loads once both
+        // passengers got off
+        let unload = scheduler.next_event().unwrap();
+        assert_eq!(unload.get_time_stamp(), 10);
+        bus_world
+            .apply_event(&mut scheduler, &mut stats_recorder, unload.as_ref())
+            .unwrap();
+        assert_eq!(scheduler.peek_time(), Some(10 + 2 * 3));
+    }
+
+    #[test]
+    fn passengers_need_two_stops() {
+        let mut bus_world = BusEnvironment::new(BusEnvironmentSettings::default());
+        bus_world.create_bus_stops(1);
+        assert!(matches!(
+            bus_world.initialize_bus_stops_with_passengers(10),
+            Err(SimError::InvalidState(_))
         ));
-        bus_world.apply_event(&mut scheduler, &mut stats_recorder, event);
-        assert_eq!(bus_world.bus_stops.len(), 1);
-        assert_eq!(bus_world.bus_stops[0].buses_at_stop.len(), 1);
     }
 }
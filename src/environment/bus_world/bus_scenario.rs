@@ -0,0 +1,1306 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::environment::bus_world::bus::{Bus, RouteKind};
+use crate::environment::bus_world::bus_environment::{BusEnvironment, BusEnvironmentSettings};
+use crate::environment::bus_world::bus_world_events::bus_event::BusEvent;
+use crate::environment::bus_world::bus_world_events::import_bus::{
+    ImportBusEvent, ImportBusesJson,
+};
+use crate::environment::bus_world::journey_planner::JourneyPlanner;
+use crate::environment::bus_world::passenger_arrivals::{ArrivalProcess, PassengerGenerator};
+use crate::environment::bus_world::route_network::{Link, RouteNetwork};
+use crate::event::event::Event;
+use crate::genetic_learning::evolution::{Evolvable, Population};
+use crate::simulation::sim::Simulation;
+use crate::simulation::sim_error::SimError;
+
+/// A bus world scenario, usually read from a TOML or JSON file:
+///
+/// ```toml
+/// runtime = 200
+/// seed = 7
+/// stops = ["A", "B", "C"]
+///
+/// [settings]
+/// pickup_delay = 1
+///
+/// [routes]
+/// red = ["A", "B", "C"]
+/// blue = { stops = ["C", "B", "A"], kind = "loop", layover = 5 }
+///
+/// [[fleet]]
+/// route = "red"
+/// capacity = 5
+/// count = 2
+/// start_time = 0
+///
+/// [[demand]]
+/// route = "red"
+/// passengers = 40
+///
+/// [[demand]]
+/// from = "A"
+/// to = "C"
+/// passengers = 3
+///
+/// [[links]]
+/// from = "A"
+/// to = "B"
+/// distance = 1.5
+/// travel_time = { distribution = "uniform", min = 4, max = 6 }
+///
+/// [[links]]
+/// from = "B"
+/// to = "C"
+/// travel_time = { distribution = "fixed", time = 3 }
+///
+/// [[arrivals]]
+/// stop = "A"
+/// process = { kind = "poisson", rate = 0.2 }
+///
+/// [[arrivals]]
+/// stop = "B"
+/// process = { kind = "profile", period = 60, rates = [0.1, 0.5, 0.2] }
+///
+/// [od_matrix.A]
+/// B = 1.0
+/// C = 3.0
+/// ```
+///
+/// Without `links` every hop takes `next_stop_delay`. With them, every
+/// consecutive pair of stops on a route needs a link.
+///
+/// Passengers arriving during the run head to a stop drawn from the row
+/// of `od_matrix` for their stop, weighted by its values, or to any other
+/// stop if the matrix has no row for it.
+///
+/// Passengers whose destination no single route reaches change buses on
+/// the way, with as few changes as the routes of the fleet allow. Demand
+/// and `od_matrix` destinations no combination of routes reaches are
+/// rejected.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BusScenario {
+    pub runtime: usize,
+    /// Seeds the environment, overriding any seed given in `settings`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub stops: Vec<String>,
+    /// Named routes, each the ordered list of stops a bus visits, or a
+    /// table that also gives the [RouteKind] and layover.
+    pub routes: BTreeMap<String, Route>,
+    pub fleet: Vec<FleetEntry>,
+    #[serde(default)]
+    pub demand: Vec<Demand>,
+    #[serde(default)]
+    pub settings: BusEnvironmentSettings,
+    #[serde(default)]
+    pub links: Vec<Link>,
+    #[serde(default)]
+    pub arrivals: Vec<Arrivals>,
+    /// Weights of the destinations of passengers arriving at each stop.
+    #[serde(default)]
+    pub od_matrix: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+/// Passengers turning up at `stop` during the run.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Arrivals {
+    pub stop: String,
+    pub process: ArrivalProcess,
+}
+
+/// The stops a bus calls at, in order, and what it does at the last one.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(from = "RouteDefinition")]
+pub struct Route {
+    pub stops: Vec<String>,
+    pub kind: RouteKind,
+    /// How long buses wait at the start of every trip after the first.
+    pub layover: usize,
+}
+
+impl From<Vec<String>> for Route {
+    fn from(stops: Vec<String>) -> Self {
+        Route {
+            stops,
+            kind: RouteKind::Once,
+            layover: 0,
+        }
+    }
+}
+
+/// A route as written in a scenario: just its stops, or a full table.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RouteDefinition {
+    Stops(Vec<String>),
+    Table(RouteTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouteTable {
+    stops: Vec<String>,
+    #[serde(default)]
+    kind: RouteKind,
+    #[serde(default)]
+    layover: usize,
+}
+
+impl From<RouteDefinition> for Route {
+    fn from(definition: RouteDefinition) -> Self {
+        match definition {
+            RouteDefinition::Stops(stops) => Route::from(stops),
+            RouteDefinition::Table(table) => Route {
+                stops: table.stops,
+                kind: table.kind,
+                layover: table.layover,
+            },
+        }
+    }
+}
+
+/// `count` identical buses driving `route`, entering service at `start_time`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct FleetEntry {
+    pub route: String,
+    pub capacity: usize,
+    #[serde(default = "one")]
+    pub count: usize,
+    #[serde(default)]
+    pub start_time: usize,
+}
+
+/// Passengers waiting when the simulation starts: either random trips
+/// along a `route`, or trips between the stops `from` and `to`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Demand {
+    #[serde(default)]
+    pub route: Option<String>,
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    pub passengers: usize,
+}
+
+fn one() -> usize {
+    1
+}
+
+fn invalid(reason: String) -> SimError {
+    SimError::InvalidScenario(reason)
+}
+
+impl BusScenario {
+    pub fn from_toml_str(scenario: &str) -> Result<BusScenario, SimError> {
+        toml::from_str(scenario).map_err(|e| invalid(e.to_string()))
+    }
+
+    pub fn from_json_str(scenario: &str) -> Result<BusScenario, SimError> {
+        serde_json::from_str(scenario).map_err(|e| invalid(e.to_string()))
+    }
+
+    /// Read a scenario file, picking the format from its extension.
+    pub fn load<T: AsRef<Path>>(path: T) -> Result<BusScenario, SimError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        let mut scenario = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => BusScenario::from_toml_str(&contents)?,
+            Some("json") => BusScenario::from_json_str(&contents)?,
+            _ => {
+                return Err(invalid(format!(
+                    "{} is neither a .toml nor a .json file",
+                    path.display()
+                )))
+            }
+        };
+        // trace files are found next to the scenario
+        if let Some(directory) = path.parent() {
+            for arrivals in &mut scenario.arrivals {
+                if let ArrivalProcess::Trace {
+                    file: Some(file), ..
+                } = &mut arrivals.process
+                {
+                    *file = directory.join(&*file);
+                }
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Check that every stop and route referenced exists and that routes
+    /// and demand make sense, before anything is built.
+    pub fn validate(&self) -> Result<(), SimError> {
+        if self.stops.is_empty() {
+            return Err(invalid("no stops are defined".to_string()));
+        }
+        let mut stops = BTreeSet::new();
+        for stop in &self.stops {
+            if !stops.insert(stop.as_str()) {
+                return Err(invalid(format!("stop {} is defined twice", stop)));
+            }
+        }
+        let known_stop = |stop: &str, context: String| {
+            if stops.contains(stop) {
+                Ok(())
+            } else {
+                Err(invalid(format!("{} uses unknown stop {}", context, stop)))
+            }
+        };
+
+        for (name, route) in &self.routes {
+            if route.stops.is_empty() {
+                return Err(invalid(format!("route {} has no stops", name)));
+            }
+            if route.stops.len() == 1 {
+                return Err(invalid(format!("route {} has a single stop", name)));
+            }
+            for stop in &route.stops {
+                known_stop(stop, format!("route {}", name))?;
+            }
+        }
+
+        self.settings.validate().map_err(invalid)?;
+        let network = self.network();
+        for link in &self.links {
+            known_stop(&link.from, format!("link {}", link))?;
+            known_stop(&link.to, format!("link {}", link))?;
+            if link.from == link.to {
+                return Err(invalid(format!("link {} joins a stop to itself", link)));
+            }
+            if link.distance < 0.0 || link.distance.is_nan() {
+                return Err(invalid(format!(
+                    "link {} has an invalid distance {}",
+                    link, link.distance
+                )));
+            }
+            link.travel_time
+                .validate()
+                .map_err(|reason| invalid(format!("link {}: {}", link, reason)))?;
+        }
+        for (name, route) in &self.routes {
+            network
+                .validate_route(route.kind, &route.stops)
+                .map_err(|error| match error {
+                    SimError::MissingEntity { id, .. } => {
+                        invalid(format!("route {} has no link {}", name, id))
+                    }
+                    error => error,
+                })?;
+        }
+
+        for arrivals in &self.arrivals {
+            known_stop(&arrivals.stop, "arrivals".to_string())?;
+            arrivals
+                .process
+                .validate()
+                .map_err(|reason| invalid(format!("arrivals at {}: {}", arrivals.stop, reason)))?;
+        }
+        for (origin, row) in &self.od_matrix {
+            known_stop(origin, "od_matrix".to_string())?;
+            for (destination, weight) in row {
+                known_stop(destination, format!("od_matrix row {}", origin))?;
+                if !weight.is_finite() || *weight < 0.0 {
+                    return Err(invalid(format!(
+                        "od_matrix weight from {} to {} is invalid: {}",
+                        origin, destination, weight
+                    )));
+                }
+            }
+            if row.get(origin).is_some_and(|weight| *weight > 0.0) {
+                return Err(invalid(format!("od_matrix sends {} to itself", origin)));
+            }
+            if !row.values().any(|weight| *weight > 0.0) {
+                return Err(invalid(format!(
+                    "od_matrix row {} has no destinations",
+                    origin
+                )));
+            }
+        }
+
+        if self.fleet.is_empty() {
+            return Err(invalid("the fleet has no buses".to_string()));
+        }
+        for bus in &self.fleet {
+            if !self.routes.contains_key(&bus.route) {
+                return Err(invalid(format!("fleet uses unknown route {}", bus.route)));
+            }
+            if bus.capacity == 0 {
+                return Err(invalid(format!(
+                    "buses on route {} have no capacity",
+                    bus.route
+                )));
+            }
+            if bus.start_time > self.runtime {
+                return Err(invalid(format!(
+                    "buses on route {} start at {}, after the runtime {}",
+                    bus.route, bus.start_time, self.runtime
+                )));
+            }
+        }
+
+        for demand in &self.demand {
+            match (&demand.route, &demand.from, &demand.to) {
+                (Some(route), None, None) => match self.routes.get(route) {
+                    Some(found) if found.stops.len() < 2 => {
+                        return Err(invalid(format!(
+                            "demand on route {} needs a route with at least 2 stops",
+                            route
+                        )))
+                    }
+                    Some(_) => {}
+                    None => return Err(invalid(format!("demand uses unknown route {}", route))),
+                },
+                (None, Some(from), Some(to)) => {
+                    known_stop(from, "demand".to_string())?;
+                    known_stop(to, "demand".to_string())?;
+                    if from == to {
+                        return Err(invalid(format!("demand from {} to itself", from)));
+                    }
+                }
+                _ => {
+                    return Err(invalid(
+                        "demand needs either a route, or both from and to".to_string(),
+                    ))
+                }
+            }
+        }
+
+        // passengers may change buses, but some bus has to go each way
+        let planner = self.planner();
+        let reachable = |from: &str, to: &str, context: &str| match planner.plan(from, to) {
+            Some(_) => Ok(()),
+            None => Err(invalid(format!(
+                "{} from {} to {} cannot be reached by any bus",
+                context, from, to
+            ))),
+        };
+        for demand in &self.demand {
+            if let (Some(from), Some(to)) = (&demand.from, &demand.to) {
+                reachable(from, to, "demand")?;
+            }
+        }
+        // arrivals without an od_matrix row go to every other stop
+        let origins: BTreeSet<&String> = self
+            .od_matrix
+            .keys()
+            .chain(self.arrivals.iter().map(|arrivals| &arrivals.stop))
+            .collect();
+        for origin in origins {
+            let context = if self.od_matrix.contains_key(origin) {
+                "od_matrix"
+            } else {
+                "arrivals"
+            };
+            for (destination, weight) in self.destinations(origin) {
+                if weight > 0.0 {
+                    reachable(origin, &destination, context)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A planner over the routes the fleet drives.
+    pub fn planner(&self) -> JourneyPlanner {
+        let mut planner = JourneyPlanner::new();
+        let driven: BTreeSet<&String> = self.fleet.iter().map(|bus| &bus.route).collect();
+        for name in driven {
+            let route = &self.routes[name];
+            planner.add_route(route.stops.clone(), route.kind);
+        }
+        planner
+    }
+
+    /// The links of the scenario as a network, empty if there are none.
+    pub fn network(&self) -> RouteNetwork {
+        let mut network = RouteNetwork::new();
+        for link in &self.links {
+            network.add_link(link.clone());
+        }
+        network
+    }
+
+    /// The destinations of passengers arriving at `stop`, with their weights.
+    fn destinations(&self, stop: &str) -> Vec<(String, f64)> {
+        match self.od_matrix.get(stop) {
+            Some(row) => row
+                .iter()
+                .map(|(destination, weight)| (destination.clone(), *weight))
+                .collect(),
+            None => self
+                .stops
+                .iter()
+                .filter(|other| *other != stop)
+                .map(|other| (other.clone(), 1.0))
+                .collect(),
+        }
+    }
+
+    /// The same scenario with a different seed, e.g. for replications.
+    pub fn with_seed(&self, seed: u64) -> BusScenario {
+        BusScenario {
+            seed: Some(seed),
+            ..self.clone()
+        }
+    }
+
+    /// The settings the environment runs with, seeded by `seed` if it is set.
+    pub fn environment_settings(&self) -> BusEnvironmentSettings {
+        match self.seed {
+            Some(seed) => self.settings.with_seed(seed),
+            None => self.settings,
+        }
+    }
+
+    /// Validate the scenario and build a simulation ready to run.
+    pub fn build(&self) -> Result<Simulation<BusEvent>, SimError> {
+        let (env, fleet) = self.prepare()?;
+        self.assemble(env, fleet)
+    }
+
+    /// Validate the scenario and build its environment, with the passengers
+    /// waiting, and its fleet as `(start time, bus)` pairs. The fleet can be
+    /// changed, e.g. evolved, before [BusScenario::assemble] puts it in service.
+    pub fn prepare(&self) -> Result<(BusEnvironment, Vec<(usize, Bus)>), SimError> {
+        self.validate()?;
+        let mut env = BusEnvironment::new(self.environment_settings());
+        for stop in &self.stops {
+            env.add_bus_stop(stop.clone());
+        }
+        env.set_network(self.network());
+        env.set_planner(self.planner());
+        for arrivals in &self.arrivals {
+            let process = arrivals.process.load_trace(Path::new(""))?;
+            env.add_passenger_generator(PassengerGenerator::new(
+                arrivals.stop.clone(),
+                process,
+                self.destinations(&arrivals.stop),
+            ));
+        }
+
+        for demand in &self.demand {
+            match (&demand.route, &demand.from, &demand.to) {
+                (Some(route), _, _) => {
+                    env.add_random_passengers(&self.routes[route].stops, demand.passengers)?
+                }
+                (None, Some(from), Some(to)) => env.add_passengers(from, to, demand.passengers)?,
+                _ => unreachable!("demand was validated"),
+            }
+        }
+
+        let mut fleet = Vec::new();
+        for entry in &self.fleet {
+            for _ in 0..entry.count {
+                let route = &self.routes[&entry.route];
+                let mut bus = env
+                    .new_bus(entry.capacity)
+                    .with_route_kind(route.kind, route.layover);
+                for stop in &route.stops {
+                    bus.add_serviced_stop(stop.clone());
+                }
+                fleet.push((entry.start_time, bus));
+            }
+        }
+        Ok((env, fleet))
+    }
+
+    /// Build a simulation that puts every bus of the fleet in service at
+    /// its start time.
+    pub fn assemble(
+        &self,
+        mut env: BusEnvironment,
+        fleet: Vec<(usize, Bus)>,
+    ) -> Result<Simulation<BusEvent>, SimError> {
+        // one import event per start time, keeping the order of the fleet
+        let mut buses_by_start: BTreeMap<usize, Vec<Bus>> = BTreeMap::new();
+        for (start_time, bus) in fleet {
+            buses_by_start.entry(start_time).or_default().push(bus);
+        }
+        let mut imports =
+            buses_by_start
+                .into_iter()
+                .enumerate()
+                .map(|(uid, (start_time, buses))| {
+                    Box::new(ImportBusEvent::new(
+                        uid,
+                        start_time,
+                        ImportBusesJson::new(buses),
+                    ))
+                });
+
+        let first = imports
+            .next()
+            .ok_or_else(|| invalid("the fleet has no buses".to_string()))?;
+        let arrivals = env.first_arrivals();
+        let abandonments = env.initial_abandonments();
+        let mut sim = Simulation::new(self.runtime, Box::new(env), first);
+        for import in imports {
+            sim.add_arbitrary_event(import)?;
+        }
+        for arrival in arrivals {
+            sim.add_arbitrary_event(Box::new(arrival))?;
+        }
+        // the passengers learn their handles once the simulation owns the
+        // environment, so that boarding cancels giving up
+        let mut abandon_events = Vec::new();
+        for abandonment in abandonments {
+            let data = match abandonment.get_payload() {
+                BusEvent::PassengerAbandon(data) => data.clone(),
+                _ => unreachable!("only abandonments are scheduled here"),
+            };
+            abandon_events.push((data, sim.add_arbitrary_event(Box::new(abandonment))?));
+        }
+        if let Some(env) = sim
+            .environment
+            .as_any_mut()
+            .downcast_mut::<BusEnvironment>()
+        {
+            for (data, handle) in abandon_events {
+                env.set_abandon_event(&data.stop_name, data.passenger_uid, handle)?;
+            }
+        }
+        Ok(sim)
+    }
+
+    /// Evolve the buses of a prepared fleet for a number of generations,
+    /// seeded from the scenario. An evolved bus whose route the links do
+    /// not allow is replaced by the original bus it evolved in place of.
+    pub fn evolve_fleet(
+        &self,
+        fleet: &[(usize, Bus)],
+        generations: usize,
+    ) -> Result<Vec<(usize, Bus)>, SimError> {
+        let buses = fleet.iter().map(|(_, bus)| bus.clone()).collect();
+        let mut population = Population::new(buses, self.environment_settings().seed());
+        for _ in 0..generations {
+            population.evolve().map_err(SimError::InvalidState)?;
+        }
+        let network = self.network();
+        Ok(fleet
+            .iter()
+            .zip(population.populace)
+            .map(|((start_time, original), evolved)| {
+                let drivable = network
+                    .validate_route(evolved.route_kind, &evolved.serviced_stop_names)
+                    .is_ok();
+                (
+                    *start_time,
+                    if drivable { evolved } else { original.clone() },
+                )
+            })
+            .collect())
+    }
+
+    /// The same scenario with one numeric parameter changed, for sweeps.
+    /// `runtime` and the delays of [BusEnvironmentSettings] are set
+    /// directly; `capacity` and `count` apply to every fleet entry,
+    /// `layover` to every route and `passengers` to every demand entry.
+    pub fn with_parameter(&self, name: &str, value: usize) -> Result<BusScenario, SimError> {
+        let mut scenario = self.clone();
+        match name {
+            "runtime" => scenario.runtime = value,
+            "capacity" => scenario.fleet.iter_mut().for_each(|e| e.capacity = value),
+            "count" => scenario.fleet.iter_mut().for_each(|e| e.count = value),
+            "layover" => scenario
+                .routes
+                .values_mut()
+                .for_each(|route| route.layover = value),
+            "passengers" => scenario
+                .demand
+                .iter_mut()
+                .for_each(|d| d.passengers = value),
+            _ => {
+                scenario.settings = scenario
+                    .settings
+                    .with_delay(name, value)
+                    .ok_or_else(|| invalid(format!("unknown scenario parameter {}", name)))?
+            }
+        }
+        Ok(scenario)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BusScenario;
+    use crate::environment::bus_world::bus_environment::{
+        BusEnvironment, IN_VEHICLE_TIME, JOURNEY_TIME, LOST_DEMAND, STILL_RIDING, STILL_WAITING,
+        TRANSFERS, TRANSFER_WAIT, WAIT_TIME,
+    };
+    use crate::environment::bus_world::passenger::Passenger;
+    use crate::simulation::sim_error::SimError;
+
+    const SCENARIO: &str = r#"
+        runtime = 150
+        seed = 3
+        stops = ["A", "B", "C", "D"]
+
+        [settings]
+        pickup_delay = 2
+
+        [routes]
+        main = ["A", "B", "C", "D"]
+        short = ["B", "C"]
+
+        [[fleet]]
+        route = "main"
+        capacity = 5
+        count = 2
+
+        [[fleet]]
+        route = "short"
+        capacity = 3
+        start_time = 20
+
+        [[demand]]
+        route = "main"
+        passengers = 30
+
+        [[demand]]
+        from = "B"
+        to = "C"
+        passengers = 4
+    "#;
+
+    fn rejected(scenario: &str) -> String {
+        match BusScenario::from_toml_str(scenario).and_then(|s| s.build()) {
+            Err(SimError::InvalidScenario(reason)) => reason,
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("scenario was accepted"),
+        }
+    }
+
+    #[test]
+    fn toml_and_json_scenarios_agree() {
+        let from_toml = BusScenario::load("./test_data/scenarios/two_routes.toml").unwrap();
+        let from_json = BusScenario::load("./test_data/scenarios/two_routes.json").unwrap();
+        let mut first = from_toml.build().unwrap();
+        let mut second = from_json.build().unwrap();
+        first.run().unwrap();
+        second.run().unwrap();
+        assert_eq!(first.statistics, second.statistics);
+        assert_eq!(
+            first.environment.get_state(),
+            second.environment.get_state()
+        );
+    }
+
+    #[test]
+    fn build_places_fleet_and_demand() {
+        let scenario = BusScenario::from_toml_str(SCENARIO).unwrap();
+        let mut sim = scenario.build().unwrap();
+        // the second import event is pending at time 20
+        sim.run_until(0).unwrap();
+        let state: serde_json::Value = serde_json::from_str(&sim.environment.get_state()).unwrap();
+        let stops = state["bus_stops"].as_array().unwrap();
+        assert_eq!(stops[0]["buses_at_stop"].as_array().unwrap().len(), 2);
+        assert_eq!(stops[1]["buses_at_stop"].as_array().unwrap().len(), 0);
+        sim.run_until(20).unwrap();
+        let state: serde_json::Value = serde_json::from_str(&sim.environment.get_state()).unwrap();
+        let buses_at_b = state["bus_stops"][1]["buses_at_stop"].as_array().unwrap();
+        let short_bus = buses_at_b.iter().find(|bus| bus["capacity"] == 3).unwrap();
+        assert_eq!(
+            short_bus["serviced_stop_names"],
+            serde_json::json!(["B", "C"])
+        );
+        assert_eq!(state["settings"]["pickup_delay"], 2);
+        assert_eq!(state["settings"]["seed"], 3);
+        sim.run().unwrap();
+    }
+
+    #[test]
+    fn misspelt_settings_and_links_are_rejected() {
+        let misspelt = |from: &str, to: &str| {
+            let scenario = format!(
+                "{}{}",
+                SCENARIO,
+                r#"
+                [[links]]
+                from = "A"
+                to = "B"
+                travel_time = { distribution = "fixed", time = 3 }
+                "#
+            );
+            assert!(BusScenario::from_toml_str(&scenario).is_ok());
+            assert!(BusScenario::from_toml_str(&scenario.replace(from, to)).is_err());
+        };
+        misspelt("pickup_delay", "pickup_dealy");
+        misspelt("travel_time", "travle_time");
+        misspelt("time = 3", "tme = 3");
+    }
+
+    #[test]
+    fn settings_seed_is_kept_without_a_top_level_seed() {
+        let scenario = BusScenario::from_toml_str(&SCENARIO.replace("seed = 3\n", "")).unwrap();
+        assert_eq!(scenario.environment_settings().seed(), 0);
+        let scenario = BusScenario::from_toml_str(
+            &SCENARIO
+                .replace("seed = 3\n", "")
+                .replace("pickup_delay = 2", "pickup_delay = 2\nseed = 9"),
+        )
+        .unwrap();
+        assert_eq!(scenario.environment_settings().seed(), 9);
+        assert_eq!(scenario.with_seed(4).environment_settings().seed(), 4);
+    }
+
+    #[test]
+    fn parameters_can_be_swept() {
+        let scenario = BusScenario::from_toml_str(SCENARIO).unwrap();
+        let changed = scenario
+            .with_parameter("capacity", 9)
+            .and_then(|s| s.with_parameter("next_stop_delay", 7))
+            .and_then(|s| s.with_parameter("runtime", 300))
+            .unwrap();
+        assert!(changed.fleet.iter().all(|entry| entry.capacity == 9));
+        assert_eq!(changed.runtime, 300);
+        let settings = serde_json::to_value(changed.settings).unwrap();
+        assert_eq!(settings["next_stop_delay"], 7);
+        assert_eq!(settings["pickup_delay"], 2);
+    }
+
+    #[test]
+    fn unknown_stops_and_routes_are_rejected() {
+        let reason = rejected(&SCENARIO.replace(r#"short = ["B", "C"]"#, r#"short = ["B", "Z"]"#));
+        assert_eq!(reason, "route short uses unknown stop Z");
+
+        let reason = rejected(&SCENARIO.replace(r#"route = "short""#, r#"route = "long""#));
+        assert_eq!(reason, "fleet uses unknown route long");
+
+        let reason = rejected(&SCENARIO.replace(r#"to = "C""#, r#"to = "E""#));
+        assert_eq!(reason, "demand uses unknown stop E");
+    }
+
+    const LINKS: &str = r#"
+        [[links]]
+        from = "A"
+        to = "B"
+        distance = 2.0
+        travel_time = { distribution = "fixed", time = 4 }
+
+        [[links]]
+        from = "B"
+        to = "C"
+        travel_time = { distribution = "fixed", time = 9 }
+
+        [[links]]
+        from = "C"
+        to = "D"
+        travel_time = { distribution = "uniform", min = 1, max = 3 }
+    "#;
+
+    #[test]
+    fn buses_drive_links_at_their_travel_time() {
+        let scenario = BusScenario::from_toml_str(
+            r#"
+            runtime = 100
+            stops = ["A", "B", "C"]
+
+            [routes]
+            express = ["A", "C"]
+
+            [[fleet]]
+            route = "express"
+            capacity = 4
+
+            [[links]]
+            from = "A"
+            to = "C"
+            distance = 2.5
+            travel_time = { distribution = "fixed", time = 17 }
+            "#,
+        )
+        .unwrap();
+        let mut sim = scenario.build().unwrap();
+
+        // step until the bus leaves A, skipping the B it never serves
+        while let Some(event) = sim.step().unwrap() {
+            if event.get_event_type() == "MoveBusToStop" {
+                let left_at = event.get_time_stamp();
+                let unload = sim
+                    .upcoming_events()
+                    .into_iter()
+                    .map(|(_, event)| event)
+                    .find(|event| event.get_event_type() == "UnloadPassengers")
+                    .unwrap();
+                assert_eq!(unload.get_time_stamp(), left_at + 17);
+                break;
+            }
+        }
+        let distance = sim
+            .statistics
+            .all_series
+            .iter()
+            .find(|series| series.statistic_label.ends_with(": Distance"))
+            .unwrap();
+        assert_eq!(distance.sum(), 2.5);
+        sim.run().unwrap();
+    }
+
+    #[test]
+    fn looping_buses_run_until_the_end() {
+        let scenario = |kind: &str| {
+            BusScenario::from_toml_str(&format!(
+                r#"
+                runtime = 300
+                stops = ["A", "B", "C"]
+
+                [routes]
+                circle = {{ stops = ["A", "B", "C"], kind = "{}", layover = 7 }}
+
+                [[fleet]]
+                route = "circle"
+                capacity = 4
+                "#,
+                kind
+            ))
+            .unwrap()
+        };
+        // times at which the bus pulled away from A
+        let departures_from_a = |kind: &str| -> Vec<usize> {
+            let mut sim = scenario(kind).build().unwrap();
+            sim.run().unwrap();
+            let series = sim
+                .statistics
+                .get_series_by_name("stop A: buses".to_string())
+                .unwrap();
+            series
+                .observations()
+                .filter(|(_, buses)| *buses == 0.0)
+                .map(|(time, _)| time)
+                .collect()
+        };
+
+        assert_eq!(departures_from_a("once").len(), 1);
+        let looping = departures_from_a("loop");
+        assert!(looping.len() > 5);
+        assert!(*looping.last().unwrap() > 250);
+        // three hops of 5 and the layover between trips
+        assert_eq!(looping[2] - looping[1], 3 * 5 + 7);
+        assert!(departures_from_a("out_and_back").len() > 5);
+        assert!(departures_from_a("return_to_depot").len() > 5);
+    }
+
+    #[test]
+    fn passengers_arrive_during_the_run() {
+        let arrivals = r#"
+            [[arrivals]]
+            stop = "A"
+            process = { kind = "poisson", rate = 0.2 }
+
+            [[arrivals]]
+            stop = "B"
+            process = { kind = "profile", period = 50, rates = [0.0, 0.5] }
+
+            [od_matrix.A]
+            C = 1.0
+            D = 1.0
+
+            [od_matrix.B]
+            C = 1.0
+        "#;
+        let scenario = BusScenario::from_toml_str(&format!("{}{}", SCENARIO, arrivals)).unwrap();
+        let mut sim = scenario.build().unwrap();
+        sim.run().unwrap();
+
+        let arrivals_at = |stop: &str| -> Vec<usize> {
+            sim.statistics
+                .get_series_by_name(format!("stop {}: arrivals", stop))
+                .map(|series| series.observations().map(|(time, _)| time).collect())
+                .unwrap_or_default()
+        };
+        let at_a = arrivals_at("A");
+        assert!(at_a.len() > 15 && at_a.len() < 45);
+        assert!(*at_a.last().unwrap() > 100);
+        // nobody arrives at B in the quiet periods of its profile
+        let at_b = arrivals_at("B");
+        assert!(!at_b.is_empty());
+        assert!(at_b
+            .iter()
+            .all(|time| (51..=100).contains(time) || *time > 150));
+
+        // and the passengers arriving at A only go where the matrix sends
+        // them, unlike those waiting there from the start
+        assert!(at_a.iter().all(|time| *time > 0));
+        let env = sim
+            .environment
+            .as_any()
+            .downcast_ref::<BusEnvironment>()
+            .unwrap();
+        let from_a: Vec<&Passenger> = env
+            .passengers()
+            .into_iter()
+            .filter(|p| p.source == "A" && p.arrival_time > 0)
+            .collect();
+        assert_eq!(from_a.len(), at_a.len());
+        assert!(from_a
+            .iter()
+            .all(|p| p.destination == "C" || p.destination == "D"));
+    }
+
+    #[test]
+    fn arrivals_can_be_replayed_from_a_trace() {
+        let scenario = BusScenario::load("./test_data/scenarios/trace_arrivals.toml").unwrap();
+        let mut sim = scenario.build().unwrap();
+        sim.run().unwrap();
+        let series = sim
+            .statistics
+            .get_series_by_name("stop A: arrivals".to_string())
+            .unwrap();
+        let times: Vec<usize> = series.observations().map(|(time, _)| time).collect();
+        assert_eq!(times, vec![3, 3, 10, 42]);
+        let state: serde_json::Value = serde_json::from_str(&sim.environment.get_state()).unwrap();
+        assert_eq!(
+            state["bus_stops"][2]["completed_passengers"]
+                .as_array()
+                .unwrap()
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn bad_arrivals_are_rejected() {
+        let reason = rejected(&format!(
+            "{}{}",
+            SCENARIO,
+            r#"
+            [[arrivals]]
+            stop = "A"
+            process = { kind = "poisson", rate = -1.0 }
+            "#
+        ));
+        assert_eq!(
+            reason,
+            "arrivals at A: poisson arrivals have an invalid rate -1"
+        );
+
+        let reason = rejected(&format!("{}{}", SCENARIO, "[od_matrix.A]\nZ = 1.0\n"));
+        assert_eq!(reason, "od_matrix row A uses unknown stop Z");
+
+        let reason = rejected(&format!("{}{}", SCENARIO, "[od_matrix.A]\nB = 0.0\n"));
+        assert_eq!(reason, "od_matrix row A has no destinations");
+    }
+
+    #[test]
+    fn passenger_times_are_measured_per_passenger() {
+        let scenario = BusScenario::from_toml_str(
+            r#"
+            runtime = 100
+            stops = ["A", "B"]
+
+            [routes]
+            line = ["A", "B"]
+
+            [[fleet]]
+            route = "line"
+            capacity = 5
+
+            [[demand]]
+            from = "A"
+            to = "B"
+            passengers = 2
+
+            [[arrivals]]
+            stop = "A"
+            process = { kind = "trace", times = [15] }
+
+            [od_matrix.A]
+            B = 1.0
+            "#,
+        )
+        .unwrap();
+        let mut sim = scenario.build().unwrap();
+        sim.run().unwrap();
+        let times = |label: &str| -> Vec<f64> {
+            sim.statistics
+                .get_series_by_name(label.to_string())
+                .map(|series| series.observations().map(|(_, value)| value).collect())
+                .unwrap_or_default()
+        };
+        // the bus opens its doors at 10, both board and it leaves at 12,
+        // arriving at B after the 5 it takes to get there
+        assert_eq!(times(WAIT_TIME), vec![10.0, 10.0]);
+        assert_eq!(times(IN_VEHICLE_TIME), vec![7.0, 7.0]);
+        assert_eq!(times(JOURNEY_TIME), vec![17.0, 17.0]);
+        // the bus is gone by the time the third passenger turns up
+        assert_eq!(times(STILL_WAITING), vec![1.0]);
+        assert_eq!(times(STILL_RIDING), vec![0.0]);
+    }
+
+    #[test]
+    fn passengers_change_buses_on_the_way() {
+        let scenario = BusScenario::from_toml_str(
+            r#"
+            runtime = 200
+            stops = ["A", "B", "C"]
+
+            [routes]
+            red = ["A", "B"]
+            blue = { stops = ["B", "C"], kind = "loop" }
+
+            [[fleet]]
+            route = "red"
+            capacity = 5
+
+            [[fleet]]
+            route = "blue"
+            capacity = 5
+
+            [[demand]]
+            from = "A"
+            to = "C"
+            passengers = 3
+            "#,
+        )
+        .unwrap();
+        let mut sim = scenario.build().unwrap();
+        sim.run().unwrap();
+        let times = |label: &str| -> Vec<f64> {
+            sim.statistics
+                .get_series_by_name(label.to_string())
+                .map(|series| series.observations().map(|(_, value)| value).collect())
+                .unwrap_or_default()
+        };
+        // everyone rode red to B and blue on to C
+        assert_eq!(times(TRANSFERS), vec![1.0, 1.0, 1.0]);
+        assert_eq!(times(TRANSFER_WAIT).len(), 3);
+        assert_eq!(times(WAIT_TIME).len(), 3);
+        assert_eq!(times(JOURNEY_TIME).len(), 3);
+        assert_eq!(times(STILL_WAITING), vec![0.0]);
+
+        let reason = rejected(&SCENARIO.replace("from = \"B\"", "from = \"D\""));
+        assert_eq!(reason, "demand from D to C cannot be reached by any bus");
+        // arrivals without an od_matrix row head for every other stop
+        let arrivals = r#"
+            [[arrivals]]
+            stop = "B"
+            process = { kind = "poisson", rate = 0.2 }
+        "#;
+        let reason = rejected(&format!("{}{}", SCENARIO, arrivals));
+        assert_eq!(reason, "arrivals from B to A cannot be reached by any bus");
+    }
+
+    #[test]
+    fn impatient_passengers_are_lost() {
+        let scenario = |settings: &str| {
+            let scenario = BusScenario::from_toml_str(&format!(
+                r#"
+                runtime = 100
+                stops = ["A", "B"]
+
+                [settings]
+                initial_delay = 10
+                {}
+
+                [routes]
+                line = {{ stops = ["A", "B"], kind = "loop" }}
+
+                [[fleet]]
+                route = "line"
+                capacity = 2
+
+                [[demand]]
+                from = "A"
+                to = "B"
+                passengers = 3
+
+                [[arrivals]]
+                stop = "A"
+                process = {{ kind = "trace", times = [1, 3, 4, 30] }}
+
+                [od_matrix.A]
+                B = 1.0
+                "#,
+                settings
+            ))
+            .unwrap();
+            let mut sim = scenario.build().unwrap();
+            sim.run().unwrap();
+            let count = |label: &str| {
+                sim.statistics
+                    .get_series_by_name(label.to_string())
+                    .map_or(0.0, |series| series.sum())
+            };
+            (
+                count(LOST_DEMAND),
+                count("stop A: balked"),
+                count("stop A: reneged"),
+                count(WAIT_TIME),
+            )
+        };
+
+        let (lost, balked, reneged, _) = scenario("");
+        assert_eq!((lost, balked, reneged), (0.0, 0.0, 0.0));
+        // with 3 waiting already, the first arrival joins the queue and the
+        // next two walk away
+        let (lost, balked, reneged, _) = scenario("balk_queue = 4");
+        assert_eq!((lost, balked, reneged), (2.0, 2.0, 0.0));
+        // the bus opens its doors at 10, after those waiting since 0 and
+        // the arrival at 1 gave up, and takes the arrivals at 3 and 4
+        let (lost, balked, reneged, wait) =
+            scenario(r#"max_wait = { distribution = "fixed", time = 8 }"#);
+        assert_eq!((lost, balked, reneged), (4.0, 0.0, 4.0));
+        assert!(wait > 0.0);
+
+        let reason = rejected(&SCENARIO.replace(
+            "pickup_delay = 2",
+            r#"max_wait = { distribution = "uniform", min = 9, max = 3 }"#,
+        ));
+        assert_eq!(
+            reason,
+            "max_wait: uniform travel time has min 9 above max 3"
+        );
+    }
+
+    /// Passengers waiting at A, all picked up by a bus looping A and B.
+    fn patient_scenario(settings: &str) -> BusScenario {
+        BusScenario::from_toml_str(&format!(
+            r#"
+            runtime = 100
+            stops = ["A", "B"]
+
+            [settings]
+            {}
+
+            [routes]
+            line = {{ stops = ["A", "B"], kind = "loop" }}
+
+            [[fleet]]
+            route = "line"
+            capacity = 10
+
+            [[demand]]
+            from = "A"
+            to = "B"
+            passengers = 3
+
+            [[arrivals]]
+            stop = "A"
+            process = {{ kind = "trace", times = [1, 3, 4, 30] }}
+
+            [od_matrix.A]
+            B = 1.0
+            "#,
+            settings
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn boarding_cancels_giving_up() {
+        let run = |settings: &str| {
+            let mut sim = patient_scenario(settings).build().unwrap();
+            sim.run().unwrap();
+            sim
+        };
+        // everyone boards long before they would give up at 60 and later,
+        // so no abandonment is left to apply
+        let patient = run(r#"max_wait = { distribution = "fixed", time = 60 }"#);
+        let unlimited = run("");
+        assert_eq!(patient.event_count(), unlimited.event_count());
+        assert!(patient
+            .statistics
+            .get_series_by_name(LOST_DEMAND.to_string())
+            .is_none());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn abandonment_handles_are_replayed() {
+        use std::fs;
+        use std::io::BufReader;
+
+        use crate::simulation::trace::{replay, JsonLinesTrace};
+
+        let path = std::env::temp_dir().join("des_abandonment_handles_are_replayed.jsonl");
+        let mut sim = patient_scenario(r#"max_wait = { distribution = "fixed", time = 60 }"#)
+            .build()
+            .unwrap();
+        sim.record_trace(Box::new(JsonLinesTrace::create(&path, true).unwrap()))
+            .unwrap();
+        sim.run().unwrap();
+        sim.take_trace().unwrap();
+
+        // the handles kept by waiting passengers come out the same on replay
+        let file = BufReader::new(fs::File::open(&path).unwrap());
+        let replayed = replay(&mut BusEnvironment::default(), file).unwrap();
+        assert!(replayed.verified());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn evolved_fleets_keep_to_the_links() {
+        let scenario = BusScenario::load("./scenarios/network.toml").unwrap();
+        let network = scenario.network();
+        let (env, fleet) = scenario.prepare().unwrap();
+        // without the check, five generations breed a bus driving B->D
+        let evolved = scenario.evolve_fleet(&fleet, 5).unwrap();
+        assert_eq!(evolved.len(), fleet.len());
+        for (_, bus) in &evolved {
+            assert!(network
+                .validate_route(bus.route_kind, &bus.serviced_stop_names)
+                .is_ok());
+        }
+        scenario.assemble(env, evolved).unwrap().run().unwrap();
+    }
+
+    #[test]
+    fn missing_and_bad_links_are_rejected() {
+        let links = LINKS.replace(r#"from = "C""#, r#"from = "A""#);
+        let reason = rejected(&format!("{}{}", SCENARIO, links));
+        assert_eq!(reason, "route main has no link C->D");
+
+        let looping = SCENARIO.replace(
+            r#"short = ["B", "C"]"#,
+            r#"short = { stops = ["B", "C"], kind = "loop" }"#,
+        );
+        let reason = rejected(&format!("{}{}", looping, LINKS));
+        assert_eq!(reason, "route short has no link C->B");
+
+        let links = LINKS.replace("min = 1, max = 3", "min = 3, max = 1");
+        let reason = rejected(&format!("{}{}", SCENARIO, links));
+        assert_eq!(
+            reason,
+            "link C->D: uniform travel time has min 3 above max 1"
+        );
+
+        let links = LINKS.replace(r#"to = "D""#, r#"to = "Z""#);
+        let reason = rejected(&format!("{}{}", SCENARIO, links));
+        assert_eq!(reason, "link C->Z uses unknown stop Z");
+    }
+
+    #[test]
+    fn empty_routes_and_bad_demand_are_rejected() {
+        let reason = rejected(&SCENARIO.replace(r#"short = ["B", "C"]"#, "short = []"));
+        assert_eq!(reason, "route short has no stops");
+        let reason = rejected(&SCENARIO.replace(r#"short = ["B", "C"]"#, r#"short = ["B"]"#));
+        assert_eq!(reason, "route short has a single stop");
+
+        let reason = rejected(&SCENARIO.replace(r#"from = "B""#, r#"route = "main""#));
+        assert_eq!(reason, "demand needs either a route, or both from and to");
+
+        let reason = rejected(&SCENARIO.replace("capacity = 3", "capacity = 0"));
+        assert_eq!(reason, "buses on route short have no capacity");
+
+        let scenario = BusScenario::from_toml_str(SCENARIO).unwrap();
+        assert!(matches!(
+            scenario.with_parameter("speed", 1),
+            Err(SimError::InvalidScenario(_))
+        ));
+
+        // typos in field names are caught too
+        assert!(
+            rejected(&SCENARIO.replace("passengers = 4", "pasengers = 4")).contains("pasengers")
+        );
+    }
+}
@@ -1,42 +1,75 @@
-use crate::{des::des::Scheduler, event::event::Event, statistics::stats::Stats};
+use crate::{
+    des::des::Scheduler, event::event::Event, simulation::sim_error::SimError,
+    statistics::stats::Stats,
+};
+
+use super::bus_world_events::{
+    bus_event::BusEvent, import_bus::ImportBusesJson, load_passengers::LoadPassengersJson,
+    move_bus_to_stop::BusToStopMappingJson, new_bus::NewBusesJson,
+    passenger_abandon::PassengerAbandonJson, passenger_arrival::PassengerArrivalJson,
+    unload_passengers::UnloadPassengersJson,
+};
 
 pub trait PassengerTransportHandler {
     fn load_passengers(
         &mut self,
-        _scheduler: &mut Scheduler,
+        _scheduler: &mut Scheduler<BusEvent>,
         stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    );
+        event: &dyn Event<BusEvent>,
+        data: &LoadPassengersJson,
+    ) -> Result<(), SimError>;
 
     fn unload_passengers(
         &mut self,
-        _scheduler: &mut Scheduler,
+        _scheduler: &mut Scheduler<BusEvent>,
         stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    );
+        event: &dyn Event<BusEvent>,
+        data: &UnloadPassengersJson,
+    ) -> Result<(), SimError>;
 }
 
 pub trait AdvanceVehicleHandler {
     fn advance_bus_to_next_stop(
         &mut self,
-        scheduler: &mut Scheduler,
+        scheduler: &mut Scheduler<BusEvent>,
         stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    );
+        event: &dyn Event<BusEvent>,
+        data: &BusToStopMappingJson,
+    ) -> Result<(), SimError>;
 }
 
 pub trait NewVehicleHandler {
     fn create_new_bus(
         &mut self,
-        scheduler: &mut Scheduler,
+        scheduler: &mut Scheduler<BusEvent>,
         stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    );
+        event: &dyn Event<BusEvent>,
+        data: &NewBusesJson,
+    ) -> Result<(), SimError>;
 
     fn import_buses(
         &mut self,
-        scheduler: &mut Scheduler,
+        scheduler: &mut Scheduler<BusEvent>,
+        stat_recorder: &mut Stats,
+        event: &dyn Event<BusEvent>,
+        data: &ImportBusesJson,
+    ) -> Result<(), SimError>;
+}
+
+pub trait PassengerDemandHandler {
+    fn passenger_arrival(
+        &mut self,
+        scheduler: &mut Scheduler<BusEvent>,
+        stat_recorder: &mut Stats,
+        event: &dyn Event<BusEvent>,
+        data: &PassengerArrivalJson,
+    ) -> Result<(), SimError>;
+
+    fn passenger_abandon(
+        &mut self,
+        scheduler: &mut Scheduler<BusEvent>,
         stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    );
+        event: &dyn Event<BusEvent>,
+        data: &PassengerAbandonJson,
+    ) -> Result<(), SimError>;
 }
@@ -1,17 +1,21 @@
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     fmt::{Display, Error, Formatter},
 };
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::{bus::Bus, passenger::Passenger};
 
-#[derive(Serialize)]
+#[derive(Deserialize, Serialize)]
 pub struct BusStop {
     pub name: String,
-    pub waiting_passengers: HashMap<String, Vec<Passenger>>,
+    // ordered so that boarding and serialization are reproducible for a seed
+    pub waiting_passengers: BTreeMap<String, Vec<Passenger>>,
     pub completed_passengers: Vec<Passenger>,
+    /// Passengers who gave up: balked at the queue or stopped waiting.
+    #[serde(default)]
+    pub lost_passengers: Vec<Passenger>,
     pub buses_at_stop: Vec<Bus>,
 }
 
@@ -19,30 +23,43 @@ impl BusStop {
     pub fn new(name: String) -> BusStop {
         BusStop {
             name,
-            waiting_passengers: HashMap::new(),
+            waiting_passengers: BTreeMap::new(),
             completed_passengers: Vec::new(),
+            lost_passengers: Vec::new(),
             buses_at_stop: Vec::new(),
         }
     }
 
+    /// Queue a passenger for a bus to where their current leg ends.
     pub fn add_passenger(&mut self, passenger: Passenger) {
         self.waiting_passengers
-            .entry(passenger.destination.clone())
-            .or_insert_with(Vec::new)
+            .entry(passenger.leg_destination().clone())
+            .or_default()
             .push(passenger);
     }
 
+    /// How many passengers are waiting for a bus to `destination`.
+    pub fn queue_length(&self, destination: &str) -> usize {
+        self.waiting_passengers.get(destination).map_or(0, Vec::len)
+    }
+
+    /// Take a waiting passenger out of their queue.
+    pub fn remove_waiting_passenger(&mut self, uid: usize) -> Option<Passenger> {
+        for queue in self.waiting_passengers.values_mut() {
+            if let Some(index) = queue.iter().position(|p| p.uid == uid) {
+                return Some(queue.remove(index));
+            }
+        }
+        None
+    }
+
     pub fn add_bus(&mut self, bus: Bus) {
         self.buses_at_stop.push(bus);
     }
 
-    pub fn drain_bus(&mut self, bus_uuid: String) -> Bus {
-        let bus_index = self
-            .buses_at_stop
-            .iter()
-            .position(|b| b.uuid == bus_uuid)
-            .unwrap();
-        self.buses_at_stop.remove(bus_index)
+    pub fn drain_bus(&mut self, bus_uuid: &str) -> Option<Bus> {
+        let bus_index = self.buses_at_stop.iter().position(|b| b.uuid == bus_uuid)?;
+        Some(self.buses_at_stop.remove(bus_index))
     }
 }
 
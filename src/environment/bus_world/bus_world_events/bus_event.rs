@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    import_bus::{ImportBusEvent, ImportBusesJson},
+    load_passengers::{LoadPassengersEvent, LoadPassengersJson},
+    move_bus_to_stop::{BusToStopMappingJson, MoveBusToStopEvent},
+    new_bus::{NewBusEvent, NewBusesJson},
+    passenger_abandon::{PassengerAbandonEvent, PassengerAbandonJson},
+    passenger_arrival::{PassengerArrivalEvent, PassengerArrivalJson},
+    terminal_event::TerminalEvent,
+    unload_passengers::{UnloadPassengersEvent, UnloadPassengersJson},
+};
+use crate::event::event::{Event, EventPayload};
+
+/// Payload of every event in the bus world. The [BusEnvironment] dispatches
+/// on this directly, so no parsing happens while the simulation runs.
+///
+/// [BusEnvironment]: crate::environment::bus_world::bus_environment::BusEnvironment
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub enum BusEvent {
+    Terminal,
+    ImportBus(ImportBusesJson),
+    NewBus(NewBusesJson),
+    MoveBusToStop(BusToStopMappingJson),
+    LoadPassengers(LoadPassengersJson),
+    UnloadPassengers(UnloadPassengersJson),
+    PassengerArrival(PassengerArrivalJson),
+    PassengerAbandon(PassengerAbandonJson),
+}
+
+impl EventPayload for BusEvent {
+    fn into_event(self, uid: usize, timestamp: usize) -> Box<dyn Event<BusEvent>> {
+        match self {
+            BusEvent::Terminal => Box::new(TerminalEvent::new(uid, timestamp)),
+            BusEvent::ImportBus(data) => Box::new(ImportBusEvent::new(uid, timestamp, data)),
+            BusEvent::NewBus(data) => Box::new(NewBusEvent::new(uid, timestamp, data)),
+            BusEvent::MoveBusToStop(data) => {
+                Box::new(MoveBusToStopEvent::new(uid, timestamp, data))
+            }
+            BusEvent::LoadPassengers(data) => {
+                Box::new(LoadPassengersEvent::new(uid, timestamp, data))
+            }
+            BusEvent::UnloadPassengers(data) => {
+                Box::new(UnloadPassengersEvent::new(uid, timestamp, data))
+            }
+            BusEvent::PassengerArrival(data) => {
+                Box::new(PassengerArrivalEvent::new(uid, timestamp, data))
+            }
+            BusEvent::PassengerAbandon(data) => {
+                Box::new(PassengerAbandonEvent::new(uid, timestamp, data))
+            }
+        }
+    }
+}
@@ -2,51 +2,57 @@ use std::fmt::{Display, Error, Formatter};
 
 use serde::{Deserialize, Serialize};
 
+use super::bus_event::BusEvent;
 use crate::{environment::bus_world::bus::Bus, event::event::Event};
 
-#[derive(Serialize)]
+// (de)serialized as a plain array of buses
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(transparent)]
 pub struct ImportBusesJson {
     pub buses: Vec<Bus>,
 }
 
-impl<'de> Deserialize<'de> for ImportBusesJson {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        Ok(ImportBusesJson {
-            buses: Vec::deserialize(deserializer)?,
-        })
+impl ImportBusesJson {
+    pub fn new(buses: Vec<Bus>) -> Self {
+        Self { buses }
     }
 }
 
 pub struct ImportBusEvent {
     pub uid: usize,
     pub timestamp: usize,
-    pub data: String,
+    pub payload: BusEvent,
 }
 
 impl ImportBusEvent {
-    pub fn new(uid: usize, timestamp: usize, data: String) -> Self {
+    pub fn new(uid: usize, timestamp: usize, data: ImportBusesJson) -> Self {
         Self {
             uid,
             timestamp,
-            data,
+            payload: BusEvent::ImportBus(data),
         }
     }
+
+    /// Build the event from a JSON array of buses, e.g. one exported
+    /// from a previous run or produced by evolution.
+    #[cfg(feature = "json")]
+    pub fn from_json(uid: usize, timestamp: usize, json: &str) -> Result<Self, serde_json::Error> {
+        Ok(Self::new(uid, timestamp, serde_json::from_str(json)?))
+    }
 }
 
 impl Display for ImportBusEvent {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         write!(
             f,
-            "ImportBusEvent: uid: {}, data: {:?}",
-            self.uid, self.data
+            "ImportBusEvent: uid: {}, data: {}",
+            self.uid,
+            self.payload_text()
         )
     }
 }
 
-impl Event for ImportBusEvent {
+impl Event<BusEvent> for ImportBusEvent {
     fn get_event_type(&self) -> &str {
         "ImportBus"
     }
@@ -59,7 +65,11 @@ impl Event for ImportBusEvent {
         self.timestamp
     }
 
-    fn get_data(&self) -> Result<String, serde_json::Error> {
-        Ok(self.data.clone())
+    fn set_time_stamp(&mut self, timestamp: usize) {
+        self.timestamp = timestamp;
+    }
+
+    fn get_payload(&self) -> &BusEvent {
+        &self.payload
     }
 }
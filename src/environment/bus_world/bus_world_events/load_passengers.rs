@@ -2,15 +2,16 @@ use std::fmt::{Display, Error, Formatter};
 
 use serde::{Deserialize, Serialize};
 
+use super::bus_event::BusEvent;
 use crate::event::event::Event;
 
 pub struct LoadPassengersEvent {
     uid: usize,
     timestamp: usize,
-    data: String,
+    payload: BusEvent,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct LoadPassengersJson {
     pub bus_uuid: String, // what else?
 }
@@ -22,16 +23,16 @@ impl LoadPassengersJson {
 }
 
 impl LoadPassengersEvent {
-    pub fn new(uid: usize, timestamp: usize, data: String) -> LoadPassengersEvent {
+    pub fn new(uid: usize, timestamp: usize, data: LoadPassengersJson) -> LoadPassengersEvent {
         LoadPassengersEvent {
             uid,
             timestamp,
-            data,
+            payload: BusEvent::LoadPassengers(data),
         }
     }
 }
 
-impl Event for LoadPassengersEvent {
+impl Event<BusEvent> for LoadPassengersEvent {
     fn get_event_type(&self) -> &str {
         "LoadPassengers"
     }
@@ -44,8 +45,12 @@ impl Event for LoadPassengersEvent {
         self.timestamp
     }
 
-    fn get_data(&self) -> Result<String, serde_json::Error> {
-        Ok(self.data.clone())
+    fn set_time_stamp(&mut self, timestamp: usize) {
+        self.timestamp = timestamp;
+    }
+
+    fn get_payload(&self) -> &BusEvent {
+        &self.payload
     }
 }
 
@@ -54,7 +59,8 @@ impl Display for LoadPassengersEvent {
         write!(
             f,
             "LoadPassengersEvent: uid: {} for bus uid: {}",
-            self.uid, self.data,
+            self.uid,
+            self.payload_text(),
         )
     }
 }
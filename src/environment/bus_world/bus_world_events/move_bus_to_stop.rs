@@ -1,9 +1,10 @@
 use std::fmt::{Display, Formatter};
 
+use super::bus_event::BusEvent;
 use crate::event::event::Event;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct BusToStopMappingJson {
     pub bus_uuid: String,
     pub stop_name: String,
@@ -21,20 +22,20 @@ impl BusToStopMappingJson {
 pub struct MoveBusToStopEvent {
     uid: usize,
     timestamp: usize,
-    data: String,
+    payload: BusEvent,
 }
 
 impl MoveBusToStopEvent {
-    pub fn new(uid: usize, timestamp: usize, data: String) -> MoveBusToStopEvent {
+    pub fn new(uid: usize, timestamp: usize, data: BusToStopMappingJson) -> MoveBusToStopEvent {
         MoveBusToStopEvent {
             uid,
             timestamp,
-            data,
+            payload: BusEvent::MoveBusToStop(data),
         }
     }
 }
 
-impl Event for MoveBusToStopEvent {
+impl Event<BusEvent> for MoveBusToStopEvent {
     fn get_event_type(&self) -> &str {
         "MoveBusToStop"
     }
@@ -47,8 +48,12 @@ impl Event for MoveBusToStopEvent {
         self.timestamp
     }
 
-    fn get_data(&self) -> Result<String, serde_json::Error> {
-        Ok(self.data.clone())
+    fn set_time_stamp(&mut self, timestamp: usize) {
+        self.timestamp = timestamp;
+    }
+
+    fn get_payload(&self) -> &BusEvent {
+        &self.payload
     }
 }
 
@@ -57,7 +62,8 @@ impl Display for MoveBusToStopEvent {
         write!(
             f,
             "MoveBusToStopEvent: event uid: {}, Data: {}",
-            self.uid, self.data
+            self.uid,
+            self.payload_text()
         )
     }
 }
@@ -2,9 +2,10 @@ use std::fmt::{Display, Error, Formatter};
 
 use serde::{Deserialize, Serialize};
 
+use super::bus_event::BusEvent;
 use crate::event::event::Event;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct NewBusesJson {
     pub number_of_buses: usize,
     pub capacity: usize,
@@ -22,20 +23,20 @@ impl NewBusesJson {
 pub struct NewBusEvent {
     uid: usize,
     timestamp: usize,
-    data: String,
+    payload: BusEvent,
 }
 
 impl NewBusEvent {
-    pub fn new(uid: usize, timestamp: usize, data: String) -> NewBusEvent {
+    pub fn new(uid: usize, timestamp: usize, data: NewBusesJson) -> NewBusEvent {
         NewBusEvent {
             uid,
             timestamp,
-            data,
+            payload: BusEvent::NewBus(data),
         }
     }
 }
 
-impl Event for NewBusEvent {
+impl Event<BusEvent> for NewBusEvent {
     fn get_event_type(&self) -> &str {
         "NewBus"
     }
@@ -48,8 +49,12 @@ impl Event for NewBusEvent {
         self.timestamp
     }
 
-    fn get_data(&self) -> Result<String, serde_json::Error> {
-        Ok(self.data.clone())
+    fn set_time_stamp(&mut self, timestamp: usize) {
+        self.timestamp = timestamp;
+    }
+
+    fn get_payload(&self) -> &BusEvent {
+        &self.payload
     }
 }
 
@@ -58,22 +63,35 @@ impl Display for NewBusEvent {
         write!(
             f,
             "NewBusEvent: uid: {} for bus uid: {}",
-            self.uid, self.data,
+            self.uid,
+            self.payload_text(),
         )
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::environment::bus_world::bus_world_events::new_bus::NewBusEvent;
+    use crate::environment::bus_world::bus_world_events::bus_event::BusEvent;
+    use crate::environment::bus_world::bus_world_events::new_bus::{NewBusEvent, NewBusesJson};
     use crate::event::event::Event;
 
     #[test]
     fn create_bus_event() {
-        let bus_event = NewBusEvent::new(1, 0, String::from("Hello world!"));
+        let bus_event = NewBusEvent::new(1, 0, NewBusesJson::new(2, 5));
         assert_eq!(bus_event.get_event_type(), "NewBus");
         assert_eq!(bus_event.get_uid(), 1);
         assert_eq!(bus_event.get_time_stamp(), 0);
-        assert_eq!(bus_event.get_data().unwrap(), "Hello world!");
+        assert!(matches!(
+            bus_event.get_payload(),
+            BusEvent::NewBus(NewBusesJson {
+                number_of_buses: 2,
+                capacity: 5
+            })
+        ));
+        #[cfg(feature = "json")]
+        assert_eq!(
+            bus_event.get_data().unwrap(),
+            r#"{"NewBus":{"number_of_buses":2,"capacity":5}}"#
+        );
     }
 }
@@ -0,0 +1,71 @@
+use std::fmt::{Display, Error, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use super::bus_event::BusEvent;
+use crate::event::event::Event;
+
+pub struct PassengerAbandonEvent {
+    uid: usize,
+    timestamp: usize,
+    payload: BusEvent,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PassengerAbandonJson {
+    /// The stop the passenger gives up waiting at.
+    pub stop_name: String,
+    pub passenger_uid: usize,
+}
+
+impl PassengerAbandonJson {
+    pub fn new(stop_name: String, passenger_uid: usize) -> Self {
+        Self {
+            stop_name,
+            passenger_uid,
+        }
+    }
+}
+
+impl PassengerAbandonEvent {
+    pub fn new(uid: usize, timestamp: usize, data: PassengerAbandonJson) -> PassengerAbandonEvent {
+        PassengerAbandonEvent {
+            uid,
+            timestamp,
+            payload: BusEvent::PassengerAbandon(data),
+        }
+    }
+}
+
+impl Event<BusEvent> for PassengerAbandonEvent {
+    fn get_event_type(&self) -> &str {
+        "PassengerAbandon"
+    }
+
+    fn get_uid(&self) -> usize {
+        self.uid
+    }
+
+    fn get_time_stamp(&self) -> usize {
+        self.timestamp
+    }
+
+    fn set_time_stamp(&mut self, timestamp: usize) {
+        self.timestamp = timestamp;
+    }
+
+    fn get_payload(&self) -> &BusEvent {
+        &self.payload
+    }
+}
+
+impl Display for PassengerAbandonEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(
+            f,
+            "PassengerAbandonEvent: uid: {} for passenger: {}",
+            self.uid,
+            self.payload_text(),
+        )
+    }
+}
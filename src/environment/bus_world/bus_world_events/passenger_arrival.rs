@@ -0,0 +1,67 @@
+use std::fmt::{Display, Error, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use super::bus_event::BusEvent;
+use crate::event::event::Event;
+
+pub struct PassengerArrivalEvent {
+    uid: usize,
+    timestamp: usize,
+    payload: BusEvent,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PassengerArrivalJson {
+    /// Index of the passenger generator of the environment that arrives.
+    pub generator: usize,
+}
+
+impl PassengerArrivalJson {
+    pub fn new(generator: usize) -> Self {
+        Self { generator }
+    }
+}
+
+impl PassengerArrivalEvent {
+    pub fn new(uid: usize, timestamp: usize, data: PassengerArrivalJson) -> PassengerArrivalEvent {
+        PassengerArrivalEvent {
+            uid,
+            timestamp,
+            payload: BusEvent::PassengerArrival(data),
+        }
+    }
+}
+
+impl Event<BusEvent> for PassengerArrivalEvent {
+    fn get_event_type(&self) -> &str {
+        "PassengerArrival"
+    }
+
+    fn get_uid(&self) -> usize {
+        self.uid
+    }
+
+    fn get_time_stamp(&self) -> usize {
+        self.timestamp
+    }
+
+    fn set_time_stamp(&mut self, timestamp: usize) {
+        self.timestamp = timestamp;
+    }
+
+    fn get_payload(&self) -> &BusEvent {
+        &self.payload
+    }
+}
+
+impl Display for PassengerArrivalEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(
+            f,
+            "PassengerArrivalEvent: uid: {} from generator: {}",
+            self.uid,
+            self.payload_text(),
+        )
+    }
+}
@@ -1,24 +1,25 @@
 use std::fmt::{Display, Error, Formatter};
 
+use super::bus_event::BusEvent;
 use crate::event::event::Event;
 
 pub struct TerminalEvent {
     uid: usize,
     timestamp: usize,
-    data: String,
+    payload: BusEvent,
 }
 
 impl TerminalEvent {
-    pub fn new(uid: usize, timestamp: usize, data: String) -> TerminalEvent {
+    pub fn new(uid: usize, timestamp: usize) -> TerminalEvent {
         TerminalEvent {
             uid,
             timestamp,
-            data,
+            payload: BusEvent::Terminal,
         }
     }
 }
 
-impl Event for TerminalEvent {
+impl Event<BusEvent> for TerminalEvent {
     fn get_event_type(&self) -> &str {
         "Terminal"
     }
@@ -31,8 +32,12 @@ impl Event for TerminalEvent {
         self.timestamp
     }
 
-    fn get_data(&self) -> Result<String, serde_json::Error> {
-        Ok(self.data.clone())
+    fn set_time_stamp(&mut self, timestamp: usize) {
+        self.timestamp = timestamp;
+    }
+
+    fn get_payload(&self) -> &BusEvent {
+        &self.payload
     }
 }
 
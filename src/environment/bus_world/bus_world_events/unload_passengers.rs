@@ -2,15 +2,16 @@ use std::fmt::{Display, Error, Formatter};
 
 use serde::{Deserialize, Serialize};
 
+use super::bus_event::BusEvent;
 use crate::event::event::Event;
 
 pub struct UnloadPassengersEvent {
     uid: usize,
     timestamp: usize,
-    data: String,
+    payload: BusEvent,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct UnloadPassengersJson {
     pub bus_uuid: String, // what else?
 }
@@ -22,16 +23,16 @@ impl UnloadPassengersJson {
 }
 
 impl UnloadPassengersEvent {
-    pub fn new(uid: usize, timestamp: usize, data: String) -> UnloadPassengersEvent {
+    pub fn new(uid: usize, timestamp: usize, data: UnloadPassengersJson) -> UnloadPassengersEvent {
         UnloadPassengersEvent {
             uid,
             timestamp,
-            data,
+            payload: BusEvent::UnloadPassengers(data),
         }
     }
 }
 
-impl Event for UnloadPassengersEvent {
+impl Event<BusEvent> for UnloadPassengersEvent {
     fn get_event_type(&self) -> &str {
         "UnloadPassengers"
     }
@@ -44,8 +45,12 @@ impl Event for UnloadPassengersEvent {
         self.timestamp
     }
 
-    fn get_data(&self) -> Result<String, serde_json::Error> {
-        Ok(self.data.clone())
+    fn set_time_stamp(&mut self, timestamp: usize) {
+        self.timestamp = timestamp;
+    }
+
+    fn get_payload(&self) -> &BusEvent {
+        &self.payload
     }
 }
 
@@ -54,7 +59,8 @@ impl Display for UnloadPassengersEvent {
         write!(
             f,
             "UnloadPassengersEvent: uid: {} for bus uid: {}",
-            self.uid, self.data,
+            self.uid,
+            self.payload_text(),
         )
     }
 }
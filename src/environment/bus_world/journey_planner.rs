@@ -0,0 +1,107 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use super::bus::RouteKind;
+
+/// Plans journeys over the routes of a bus world, changing buses as few
+/// times as possible.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct JourneyPlanner {
+    routes: Vec<(Vec<String>, RouteKind)>,
+}
+
+impl JourneyPlanner {
+    pub fn new() -> JourneyPlanner {
+        JourneyPlanner { routes: Vec::new() }
+    }
+
+    pub fn add_route(&mut self, stops: Vec<String>, kind: RouteKind) {
+        self.routes.push((stops, kind));
+    }
+
+    /// Every stop one bus ride away from `from`.
+    fn rides_from<'a>(&'a self, from: &'a str) -> impl Iterator<Item = &'a String> {
+        self.routes
+            .iter()
+            .flat_map(move |(stops, kind)| {
+                let position = stops.iter().position(|stop| stop == from);
+                stops.iter().enumerate().filter(move |(index, _)| {
+                    position.is_some_and(|position| match kind {
+                        // buses only ever drive these routes forwards with passengers on board
+                        RouteKind::Once | RouteKind::ReturnToDepot => *index > position,
+                        RouteKind::Loop | RouteKind::OutAndBack => *index != position,
+                    })
+                })
+            })
+            .map(|(_, stop)| stop)
+    }
+
+    /// The stops to get off at to travel from `from` to `to`, ending with
+    /// `to`, or `None` if no combination of routes gets there.
+    pub fn plan(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        // breadth first, so the first time we reach `to` takes fewest rides
+        let mut came_from: BTreeMap<&str, &str> = BTreeMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(stop) = queue.pop_front() {
+            if stop == to {
+                let mut legs = vec![to.to_string()];
+                let mut current = to;
+                while let Some(previous) = came_from.get(current).filter(|p| **p != from) {
+                    legs.push(previous.to_string());
+                    current = previous;
+                }
+                legs.reverse();
+                return Some(legs);
+            }
+            for next in self.rides_from(stop) {
+                if next != from && !came_from.contains_key(next.as_str()) {
+                    came_from.insert(next, stop);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JourneyPlanner;
+    use crate::environment::bus_world::bus::RouteKind;
+
+    fn stops(names: &str) -> Vec<String> {
+        names.chars().map(String::from).collect()
+    }
+
+    #[test]
+    fn journeys_take_the_fewest_buses() {
+        let mut planner = JourneyPlanner::new();
+        planner.add_route(stops("ABC"), RouteKind::Once);
+        planner.add_route(stops("CDE"), RouteKind::Once);
+        planner.add_route(stops("EF"), RouteKind::Once);
+        planner.add_route(stops("BE"), RouteKind::Once);
+
+        assert_eq!(planner.plan("A", "C"), Some(stops("C")));
+        assert_eq!(planner.plan("A", "D"), Some(stops("CD")));
+        // through B to E is one change fewer than through C and D
+        assert_eq!(planner.plan("A", "F"), Some(stops("BEF")));
+        assert_eq!(planner.plan("C", "A"), None);
+        assert_eq!(planner.plan("A", "Z"), None);
+    }
+
+    #[test]
+    fn route_kinds_decide_which_way_passengers_ride() {
+        let mut planner = JourneyPlanner::new();
+        planner.add_route(stops("ABC"), RouteKind::OutAndBack);
+        assert_eq!(planner.plan("C", "A"), Some(stops("A")));
+
+        let mut planner = JourneyPlanner::new();
+        planner.add_route(stops("ABC"), RouteKind::ReturnToDepot);
+        assert_eq!(planner.plan("C", "A"), None);
+
+        let mut planner = JourneyPlanner::new();
+        planner.add_route(stops("ABC"), RouteKind::Loop);
+        assert_eq!(planner.plan("C", "B"), Some(stops("B")));
+    }
+}
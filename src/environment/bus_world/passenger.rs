@@ -4,6 +4,8 @@ use fake::{faker::name::en::Name, Fake};
 use rand::{seq::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
 
+use crate::des::des::EventHandle;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Passenger {
     pub uid: usize,
@@ -11,7 +13,34 @@ pub struct Passenger {
     pub source: String,
     pub destination: String,
     pub location: String,
-    pub wait_time: u32,
+    /// When the passenger turned up at their stop.
+    #[serde(default)]
+    pub arrival_time: usize,
+    /// When the passenger first boarded a bus.
+    #[serde(default)]
+    pub boarding_time: Option<usize>,
+    /// When the passenger last got off a bus.
+    #[serde(default)]
+    pub alighting_time: Option<usize>,
+    /// The bus the passenger rode last, once they boarded.
+    #[serde(default)]
+    pub bus_uuid: Option<String>,
+    /// The stops still to get off at, ending with the destination. Empty
+    /// means going straight to the destination.
+    #[serde(default)]
+    pub legs: Vec<String>,
+    /// Changes of bus on the way.
+    #[serde(default)]
+    pub transfers: usize,
+    /// Time spent waiting at stops between buses.
+    #[serde(default)]
+    pub transfer_wait: usize,
+    /// Buses going their way that left without them because they were full.
+    #[serde(default)]
+    pub times_left_behind: usize,
+    /// When they would give up waiting, cancelled once they board.
+    #[serde(default)]
+    pub abandon_event: Option<EventHandle>,
 }
 
 impl Passenger {
@@ -22,23 +51,100 @@ impl Passenger {
             source: source.clone(),
             destination,
             location: source,
-            wait_time: 0,
+            arrival_time: 0,
+            boarding_time: None,
+            alighting_time: None,
+            bus_uuid: None,
+            legs: Vec::new(),
+            transfers: 0,
+            transfer_wait: 0,
+            times_left_behind: 0,
+            abandon_event: None,
+        }
+    }
+
+    pub fn with_arrival_time(mut self, arrival_time: usize) -> Passenger {
+        self.arrival_time = arrival_time;
+        self
+    }
+
+    /// Plan to get off at each of `legs` in turn, the last being the
+    /// destination.
+    pub fn with_legs(mut self, legs: Vec<String>) -> Passenger {
+        self.legs = legs;
+        self
+    }
+
+    /// Where the passenger gets off the next bus.
+    pub fn leg_destination(&self) -> &String {
+        self.legs.first().unwrap_or(&self.destination)
+    }
+
+    /// Board a bus, returning how long they waited for it if it is a
+    /// transfer.
+    pub fn board(&mut self, time: usize, bus_uuid: &str) -> Option<usize> {
+        self.bus_uuid = Some(bus_uuid.to_string());
+        match (self.boarding_time, self.alighting_time) {
+            (Some(_), Some(alighted)) => {
+                let wait = time.saturating_sub(alighted);
+                self.transfer_wait += wait;
+                Some(wait)
+            }
+            _ => {
+                self.boarding_time = Some(time);
+                None
+            }
         }
     }
 
-    pub fn new_random_passenger(id: usize, bus_stops: &Vec<String>) -> Passenger {
-        // Assuming bus_stops is a Vec<BusStop>
-        let mut rng = rand::thread_rng();
+    /// Get off at a stop short of the destination to wait for the next leg.
+    pub fn transfer(&mut self) {
+        if !self.legs.is_empty() {
+            self.legs.remove(0);
+        }
+        self.transfers += 1;
+    }
+
+    pub fn alight(&mut self, time: usize, stop: &str) {
+        self.alighting_time = Some(time);
+        self.location = stop.to_string();
+    }
 
+    /// Time spent at the stop before first boarding.
+    pub fn wait_time(&self) -> Option<usize> {
+        self.boarding_time
+            .map(|boarded| boarded.saturating_sub(self.arrival_time))
+    }
+
+    /// Time spent on buses, leaving out waits between them.
+    pub fn in_vehicle_time(&self) -> Option<usize> {
+        match (self.boarding_time, self.alighting_time) {
+            (Some(boarded), Some(alighted)) => Some(
+                alighted
+                    .saturating_sub(boarded)
+                    .saturating_sub(self.transfer_wait),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Time from turning up at the stop to getting off the bus.
+    pub fn journey_time(&self) -> Option<usize> {
+        self.alighting_time
+            .map(|alighted| alighted.saturating_sub(self.arrival_time))
+    }
+
+    pub fn new_random_passenger<R: Rng + ?Sized>(
+        id: usize,
+        bus_stops: &[String],
+        rng: &mut R,
+    ) -> Passenger {
         let source_index = rng.gen_range(0..bus_stops.len() - 1);
 
         let source = bus_stops[source_index].clone();
-        let dest = bus_stops[source_index + 1..]
-            .choose(&mut rng)
-            .cloned()
-            .unwrap(); // don't panic pls
+        let dest = bus_stops[source_index + 1..].choose(rng).cloned().unwrap(); // don't panic pls
 
-        let name: String = Name().fake();
+        let name: String = Name().fake_with_rng(rng);
         Passenger::new(id, name, source, dest)
     }
 
@@ -64,6 +170,41 @@ mod test {
         assert_eq!(p.source, "A");
         assert_eq!(p.destination, "B");
         assert_eq!(p.location, "A");
-        assert_eq!(p.wait_time, 0);
+        assert_eq!(p.wait_time(), None);
+    }
+
+    #[test]
+    fn journey_times_come_from_the_timestamps() {
+        let mut p = super::Passenger::new(0, "Jane".to_string(), "A".to_string(), "C".to_string())
+            .with_arrival_time(12);
+        p.board(20, "bus");
+        assert_eq!(p.wait_time(), Some(8));
+        assert_eq!(p.in_vehicle_time(), None);
+        assert_eq!(p.journey_time(), None);
+        p.alight(45, "C");
+        assert!(p.at_destination());
+        assert_eq!(p.in_vehicle_time(), Some(25));
+        assert_eq!(p.journey_time(), Some(33));
+        assert_eq!(p.bus_uuid.as_deref(), Some("bus"));
+    }
+
+    #[test]
+    fn transfers_split_the_journey() {
+        let mut p = super::Passenger::new(0, "Jo".to_string(), "A".to_string(), "D".to_string())
+            .with_legs(vec!["B".to_string(), "D".to_string()]);
+        assert_eq!(p.leg_destination(), "B");
+        assert_eq!(p.board(10, "red"), None);
+        p.alight(15, "B");
+        assert!(!p.at_destination());
+        p.transfer();
+        assert_eq!(p.leg_destination(), "D");
+        assert_eq!(p.board(22, "blue"), Some(7));
+        p.alight(30, "D");
+        assert!(p.at_destination());
+        assert_eq!((p.transfers, p.transfer_wait), (1, 7));
+        assert_eq!(p.wait_time(), Some(10));
+        assert_eq!(p.in_vehicle_time(), Some(13));
+        assert_eq!(p.journey_time(), Some(30));
+        assert_eq!(p.bus_uuid.as_deref(), Some("blue"));
     }
 }
@@ -0,0 +1,258 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rand::distributions::WeightedIndex;
+use rand::Rng;
+use rand_distr::{Distribution, Exp};
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::sim_error::SimError;
+
+/// When passengers turn up at a stop during the run.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ArrivalProcess {
+    /// On average `rate` passengers per unit of time, independently of
+    /// each other.
+    Poisson { rate: f64 },
+    /// Like `Poisson`, but the rate changes every `period` units of time,
+    /// e.g. every hour, going through `rates` and starting over at the end.
+    Profile {
+        #[serde(default = "hour")]
+        period: usize,
+        rates: Vec<f64>,
+    },
+    /// Passengers arrive at exactly the given times, either listed in
+    /// `times` or read from `file`, one time per line.
+    Trace {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file: Option<PathBuf>,
+        #[serde(default)]
+        times: Vec<usize>,
+    },
+}
+
+fn hour() -> usize {
+    60
+}
+
+impl ArrivalProcess {
+    pub fn validate(&self) -> Result<(), String> {
+        let valid_rate = |rate: f64| rate.is_finite() && rate >= 0.0;
+        match self {
+            ArrivalProcess::Poisson { rate } if !valid_rate(*rate) => {
+                Err(format!("poisson arrivals have an invalid rate {}", rate))
+            }
+            ArrivalProcess::Profile { period: 0, .. } => {
+                Err("profile arrivals need a period above 0".to_string())
+            }
+            ArrivalProcess::Profile { rates, .. } if rates.is_empty() => {
+                Err("profile arrivals need at least one rate".to_string())
+            }
+            ArrivalProcess::Profile { rates, .. } => {
+                match rates.iter().find(|r| !valid_rate(**r)) {
+                    Some(rate) => Err(format!("profile arrivals have an invalid rate {}", rate)),
+                    None => Ok(()),
+                }
+            }
+            ArrivalProcess::Trace { times, .. } if times.windows(2).any(|t| t[0] > t[1]) => {
+                Err("trace arrival times are not in order".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The same process with a trace file read into its times. Relative
+    /// paths are taken from `base`.
+    pub fn load_trace(&self, base: &Path) -> Result<ArrivalProcess, SimError> {
+        match self {
+            ArrivalProcess::Trace {
+                file: Some(file),
+                times,
+            } => {
+                let path = base.join(file);
+                let mut times = times.clone();
+                for (number, line) in fs::read_to_string(&path)?.lines().enumerate() {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    times.push(line.parse().map_err(|_| {
+                        SimError::InvalidScenario(format!(
+                            "{} line {}: {:?} is not a time",
+                            path.display(),
+                            number + 1,
+                            line
+                        ))
+                    })?);
+                }
+                times.sort_unstable();
+                Ok(ArrivalProcess::Trace { file: None, times })
+            }
+            _ => Ok(self.clone()),
+        }
+    }
+
+    /// The rate in force at `time`, for rate based processes.
+    fn rate_at(&self, time: f64) -> f64 {
+        match self {
+            ArrivalProcess::Poisson { rate } => *rate,
+            ArrivalProcess::Profile { period, rates } => {
+                let segment = (time / *period as f64).floor() as usize;
+                rates[segment % rates.len()]
+            }
+            ArrivalProcess::Trace { .. } => 0.0,
+        }
+    }
+}
+
+/// Creates the passengers arriving at one stop, heading to destinations
+/// drawn by weight, e.g. from a row of an origin-destination matrix.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PassengerGenerator {
+    pub stop: String,
+    pub process: ArrivalProcess,
+    pub destinations: Vec<(String, f64)>,
+    /// Time of the last arrival, kept fractional so rounding to whole
+    /// event times does not change the arrival rate.
+    #[serde(default)]
+    clock: f64,
+    /// Arrivals so far, the position in a trace.
+    #[serde(default)]
+    arrivals: usize,
+}
+
+impl PassengerGenerator {
+    pub fn new(
+        stop: String,
+        process: ArrivalProcess,
+        destinations: Vec<(String, f64)>,
+    ) -> PassengerGenerator {
+        PassengerGenerator {
+            stop,
+            process,
+            destinations,
+            clock: 0.0,
+            arrivals: 0,
+        }
+    }
+
+    /// Time of the next arrival, `None` once no more passengers will come.
+    pub fn next_arrival<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<usize> {
+        if let ArrivalProcess::Trace { times, .. } = &self.process {
+            let time = times.get(self.arrivals).copied();
+            self.arrivals += 1;
+            return time;
+        }
+        let period = match self.process {
+            ArrivalProcess::Profile { period, ref rates } => {
+                if rates.iter().all(|rate| *rate <= 0.0) {
+                    return None;
+                }
+                period as f64
+            }
+            _ => f64::INFINITY,
+        };
+        // arrivals are memoryless, so when the rate changes before the next
+        // arrival we can start over from the change with the new rate
+        loop {
+            let rate = self.process.rate_at(self.clock);
+            let change = ((self.clock / period).floor() + 1.0) * period;
+            let gap = match Exp::new(rate) {
+                Ok(exp) if rate > 0.0 => exp.sample(rng),
+                _ if period.is_finite() => f64::INFINITY,
+                _ => return None,
+            };
+            if self.clock + gap < change {
+                self.clock += gap;
+                self.arrivals += 1;
+                return Some(self.clock.ceil() as usize);
+            }
+            self.clock = change;
+        }
+    }
+
+    /// Where the next passenger is headed.
+    pub fn destination<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&String> {
+        let weights = WeightedIndex::new(self.destinations.iter().map(|(_, weight)| *weight));
+        weights
+            .ok()
+            .map(|weights| &self.destinations[weights.sample(rng)].0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{ArrivalProcess, PassengerGenerator};
+    use crate::rng::sim_rng::seeded_rng;
+
+    fn arrivals_until(process: ArrivalProcess, end: usize) -> Vec<usize> {
+        let mut rng = seeded_rng(4);
+        let mut generator = PassengerGenerator::new("A".to_string(), process, Vec::new());
+        std::iter::from_fn(|| generator.next_arrival(&mut rng))
+            .take_while(|time| *time <= end)
+            .collect()
+    }
+
+    #[test]
+    fn poisson_arrivals_keep_their_rate() {
+        let times = arrivals_until(ArrivalProcess::Poisson { rate: 0.5 }, 10_000);
+        assert!((times.len() as f64 - 5000.0).abs() < 250.0);
+        assert!(times.windows(2).all(|t| t[0] <= t[1]));
+        assert!(arrivals_until(ArrivalProcess::Poisson { rate: 0.0 }, 100).is_empty());
+    }
+
+    #[test]
+    fn profiles_change_rate_every_period() {
+        let profile = ArrivalProcess::Profile {
+            period: 100,
+            rates: vec![0.0, 1.0],
+        };
+        let times = arrivals_until(profile, 4000);
+        // only the odd periods have arrivals
+        assert!(times
+            .iter()
+            .all(|time| ((*time as f64 / 100.0).ceil() as usize).is_multiple_of(2)));
+        assert!((times.len() as f64 - 2000.0).abs() < 150.0);
+    }
+
+    #[test]
+    fn traces_are_replayed() {
+        let trace = ArrivalProcess::Trace {
+            file: Some("arrivals/trace.txt".into()),
+            times: vec![1],
+        }
+        .load_trace(Path::new("./test_data"))
+        .unwrap();
+        assert_eq!(
+            trace,
+            ArrivalProcess::Trace {
+                file: None,
+                times: vec![1, 3, 3, 10, 42]
+            }
+        );
+        assert_eq!(arrivals_until(trace, 1000), vec![1, 3, 3, 10, 42]);
+
+        let unordered = ArrivalProcess::Trace {
+            file: None,
+            times: vec![4, 2],
+        };
+        assert!(unordered.validate().is_err());
+    }
+
+    #[test]
+    fn destinations_follow_their_weights() {
+        let mut rng = seeded_rng(1);
+        let generator = PassengerGenerator::new(
+            "A".to_string(),
+            ArrivalProcess::Poisson { rate: 1.0 },
+            vec![("B".to_string(), 1.0), ("C".to_string(), 3.0)],
+        );
+        let to_c = (0..1000)
+            .filter(|_| generator.destination(&mut rng).unwrap() == "C")
+            .count();
+        assert!((to_c as f64 - 750.0).abs() < 50.0);
+    }
+}
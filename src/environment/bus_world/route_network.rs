@@ -0,0 +1,177 @@
+use std::fmt::{Display, Error, Formatter};
+
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+
+use crate::environment::bus_world::bus::RouteKind;
+use crate::simulation::sim_error::SimError;
+
+/// How long it takes to drive a link, in simulated time.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "distribution", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TravelTime {
+    Fixed {
+        time: usize,
+    },
+    /// Any whole time from `min` to `max`, inclusive, equally likely.
+    Uniform {
+        min: usize,
+        max: usize,
+    },
+    /// Rounded to a whole time and never negative.
+    Normal {
+        mean: f64,
+        std_dev: f64,
+    },
+}
+
+impl TravelTime {
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        match *self {
+            TravelTime::Fixed { time } => time,
+            TravelTime::Uniform { min, max } => rng.gen_range(min..=max),
+            TravelTime::Normal { mean, std_dev } => match Normal::new(mean, std_dev) {
+                Ok(normal) => normal.sample(rng).round().max(0.0) as usize,
+                Err(_) => mean.round().max(0.0) as usize,
+            },
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            TravelTime::Fixed { .. } => Ok(()),
+            TravelTime::Uniform { min, max } if min > max => Err(format!(
+                "uniform travel time has min {} above max {}",
+                min, max
+            )),
+            TravelTime::Uniform { .. } => Ok(()),
+            TravelTime::Normal { mean, std_dev } if !(mean >= 0.0 && std_dev >= 0.0) => {
+                Err(format!(
+                    "normal travel time needs a mean and std_dev of at least 0, found {} and {}",
+                    mean, std_dev
+                ))
+            }
+            TravelTime::Normal { .. } => Ok(()),
+        }
+    }
+}
+
+/// A directed road from one stop to another.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Link {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub distance: f64,
+    pub travel_time: TravelTime,
+}
+
+impl Display for Link {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}->{}", self.from, self.to)
+    }
+}
+
+/// The stops of a bus world joined by directed links. Buses may only
+/// drive between consecutive stops of their route along a link.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct RouteNetwork {
+    pub links: Vec<Link>,
+}
+
+impl RouteNetwork {
+    pub fn new() -> RouteNetwork {
+        RouteNetwork { links: Vec::new() }
+    }
+
+    /// Add a link, replacing any link already joining the same stops.
+    pub fn add_link(&mut self, link: Link) {
+        self.links
+            .retain(|existing| !(existing.from == link.from && existing.to == link.to));
+        self.links.push(link);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    pub fn link(&self, from: &str, to: &str) -> Result<&Link, SimError> {
+        self.links
+            .iter()
+            .find(|link| link.from == from && link.to == to)
+            .ok_or_else(|| SimError::missing("Link", format!("{}->{}", from, to)))
+    }
+
+    /// Check that every hop a bus of the given kind makes along the route
+    /// is linked. Without any links every hop is allowed.
+    pub fn validate_route(&self, kind: RouteKind, route: &[String]) -> Result<(), SimError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        for (from, to) in kind.hops(route) {
+            self.link(from, to)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Link, RouteNetwork, TravelTime};
+    use crate::environment::bus_world::bus::RouteKind;
+    use crate::rng::sim_rng::seeded_rng;
+    use crate::simulation::sim_error::SimError;
+
+    fn link(from: &str, to: &str, travel_time: TravelTime) -> Link {
+        Link {
+            from: from.to_string(),
+            to: to.to_string(),
+            distance: 1.0,
+            travel_time,
+        }
+    }
+
+    #[test]
+    fn travel_times_stay_in_range() {
+        let mut rng = seeded_rng(1);
+        assert_eq!(TravelTime::Fixed { time: 4 }.sample(&mut rng), 4);
+        for _ in 0..100 {
+            let time = TravelTime::Uniform { min: 3, max: 6 }.sample(&mut rng);
+            assert!((3..=6).contains(&time));
+        }
+        let normal = TravelTime::Normal {
+            mean: 10.0,
+            std_dev: 2.0,
+        };
+        let mean = (0..1000).map(|_| normal.sample(&mut rng)).sum::<usize>() as f64 / 1000.0;
+        assert!((mean - 10.0).abs() < 0.5);
+        assert!(TravelTime::Uniform { min: 6, max: 3 }.validate().is_err());
+    }
+
+    #[test]
+    fn links_are_directed() {
+        let mut network = RouteNetwork::new();
+        network.add_link(link("A", "C", TravelTime::Fixed { time: 4 }));
+        network.add_link(link("A", "C", TravelTime::Fixed { time: 7 }));
+        assert_eq!(network.links.len(), 1);
+        assert_eq!(
+            network.link("A", "C").unwrap().travel_time,
+            TravelTime::Fixed { time: 7 }
+        );
+        assert!(matches!(
+            network.link("C", "A"),
+            Err(SimError::MissingEntity { kind: "Link", .. })
+        ));
+
+        let route = vec!["A".to_string(), "C".to_string()];
+        assert!(network.validate_route(RouteKind::Once, &route).is_ok());
+        assert!(network.validate_route(RouteKind::Loop, &route).is_err());
+        let reversed: Vec<String> = route.into_iter().rev().collect();
+        assert!(network.validate_route(RouteKind::Once, &reversed).is_err());
+        assert!(RouteNetwork::new()
+            .validate_route(RouteKind::Once, &reversed)
+            .is_ok());
+    }
+}
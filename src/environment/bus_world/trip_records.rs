@@ -0,0 +1,190 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::bus_environment::BusEnvironment;
+use super::bus_world_events::bus_event::BusEvent;
+use super::passenger::Passenger;
+use crate::environment::environment::Environment;
+use crate::simulation::observer::SimulationObserver;
+use crate::simulation::sim_error::SimError;
+use crate::statistics::export::ExportFormat;
+use crate::statistics::stats::Stats;
+
+/// The journey of one passenger who reached their destination.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct TripRecord {
+    pub uid: usize,
+    pub origin: String,
+    pub destination: String,
+    pub arrival: usize,
+    pub boarding: usize,
+    pub alighting: usize,
+    /// The bus of the last leg.
+    pub bus: String,
+    pub transfers: usize,
+    /// Time spent waiting at stops between buses.
+    pub transfer_wait: usize,
+    /// Whether a full bus going their way ever left without them.
+    pub left_behind: bool,
+}
+
+impl TripRecord {
+    /// The record of a passenger's trip, `None` until they got off a bus.
+    pub fn from_passenger(passenger: &Passenger) -> Option<TripRecord> {
+        Some(TripRecord {
+            uid: passenger.uid,
+            origin: passenger.source.clone(),
+            destination: passenger.destination.clone(),
+            arrival: passenger.arrival_time,
+            boarding: passenger.boarding_time?,
+            alighting: passenger.alighting_time?,
+            bus: passenger.bus_uuid.clone()?,
+            transfers: passenger.transfers,
+            transfer_wait: passenger.transfer_wait,
+            left_behind: passenger.times_left_behind > 0,
+        })
+    }
+}
+
+/// Write the records in `format`, a header row first for CSV.
+pub fn write_trip_records<W: Write>(
+    records: &[TripRecord],
+    mut writer: W,
+    format: ExportFormat,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()
+        }
+        ExportFormat::Json => {
+            serde_json::to_writer(&mut writer, records)?;
+            writer.flush()
+        }
+        ExportFormat::JsonLines => {
+            for record in records {
+                serde_json::to_writer(&mut writer, record)?;
+                writeln!(writer)?;
+            }
+            writer.flush()
+        }
+    }
+}
+
+/// Writes a [TripRecord] per completed passenger when the simulation ends.
+pub struct TripRecorder {
+    writer: Box<dyn Write + Send>,
+    format: ExportFormat,
+}
+
+impl TripRecorder {
+    pub fn new(writer: Box<dyn Write + Send>, format: ExportFormat) -> TripRecorder {
+        TripRecorder { writer, format }
+    }
+
+    /// Write to a file, choosing the format from its extension.
+    pub fn create<T: AsRef<Path>>(path: T) -> Result<TripRecorder, SimError> {
+        let format = ExportFormat::from_path(&path).ok_or_else(|| {
+            SimError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "cannot tell the export format of {}",
+                    path.as_ref().display()
+                ),
+            ))
+        })?;
+        let writer = BufWriter::new(File::create(path)?);
+        Ok(TripRecorder::new(Box::new(writer), format))
+    }
+}
+
+impl SimulationObserver<BusEvent> for TripRecorder {
+    fn on_terminate(
+        &mut self,
+        _time: usize,
+        environment: &dyn Environment<Payload = BusEvent>,
+        _statistics: &mut Stats,
+    ) -> Result<(), SimError> {
+        let bus_world = environment
+            .as_any()
+            .downcast_ref::<BusEnvironment>()
+            .ok_or_else(|| SimError::InvalidState("trip records need a bus world".to_string()))?;
+        write_trip_records(&bus_world.trip_records(), &mut self.writer, self.format)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use super::{write_trip_records, TripRecord, TripRecorder};
+    use crate::environment::bus_world::bus_scenario::BusScenario;
+    use crate::environment::bus_world::passenger::Passenger;
+    use crate::statistics::export::ExportFormat;
+
+    /// A writer the test can read back after the simulation owned it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn only_finished_trips_have_records() {
+        let mut passenger = Passenger::new(3, "Ann".to_string(), "A".to_string(), "C".to_string())
+            .with_arrival_time(2);
+        assert_eq!(TripRecord::from_passenger(&passenger), None);
+        passenger.board(5, "bus-1");
+        passenger.times_left_behind = 1;
+        passenger.alight(9, "C");
+        let record = TripRecord::from_passenger(&passenger).unwrap();
+
+        let mut output = Vec::new();
+        write_trip_records(&[record], &mut output, ExportFormat::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "uid,origin,destination,arrival,boarding,alighting,bus,transfers,transfer_wait,left_behind\n\
+             3,A,C,2,5,9,bus-1,0,0,true\n"
+        );
+    }
+
+    #[test]
+    fn records_are_written_when_the_run_ends() {
+        let scenario = BusScenario::load("./test_data/scenarios/two_routes.toml").unwrap();
+        let mut sim = scenario.build().unwrap();
+        let output = Shared::default();
+        sim.add_observer(Box::new(TripRecorder::new(
+            Box::new(output.clone()),
+            ExportFormat::JsonLines,
+        )));
+        sim.run().unwrap();
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let records: Vec<TripRecord> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(!records.is_empty());
+        assert!(records.windows(2).all(|pair| pair[0].uid < pair[1].uid));
+        assert!(records
+            .iter()
+            .all(|r| r.arrival <= r.boarding && r.boarding < r.alighting));
+        // four buses with room for 25 cannot take 70 passengers at once
+        assert!(records.iter().any(|r| r.left_behind));
+    }
+}
@@ -1,14 +1,38 @@
+use std::any::Any;
 use std::fmt::Display;
 
-use crate::{des::des::Scheduler, event::event::Event, statistics::stats::Stats};
+use crate::{
+    des::des::Scheduler, event::event::Event, simulation::sim_error::SimError,
+    statistics::stats::Stats,
+};
+
+pub trait Environment: Display + Send {
+    /// The typed payload carried by every event this environment handles.
+    type Payload;
 
-pub trait Environment: Display {
     fn apply_event(
         &mut self,
-        scheduler: &mut Scheduler,
+        scheduler: &mut Scheduler<Self::Payload>,
         stat_recorder: &mut Stats,
-        event: Box<dyn Event>,
-    );
+        event: &dyn Event<Self::Payload>,
+    ) -> Result<(), SimError>;
+    /// Serialized state of the environment, including any random number
+    /// generator it owns, such that [Environment::restore_state] can rebuild it.
     fn get_state(&self) -> String;
-    fn terminating_event(&self) -> Box<dyn Event>;
+    fn restore_state(&mut self, state: &str) -> Result<(), SimError>;
+    fn terminating_event(&self) -> Box<dyn Event<Self::Payload>>;
+
+    /// The environment itself, for observers written for one kind of
+    /// environment to reach its state without going through
+    /// [Environment::get_state].
+    fn as_any(&self) -> &dyn Any;
+
+    /// The environment itself, for setting up a simulation built around it.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// A short, line by line overview of the environment for live displays.
+    /// Defaults to the [Display] output.
+    fn status_lines(&self) -> Vec<String> {
+        self.to_string().lines().map(String::from).collect()
+    }
 }
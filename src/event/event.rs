@@ -1,30 +1,87 @@
-use std::{cmp::Ordering, fmt::Display};
+#[cfg(not(feature = "json"))]
+use std::fmt::Debug;
+use std::fmt::Display;
 
-pub trait Event: Display {
+use serde::{Deserialize, Serialize};
+
+/// An event carrying a typed payload `P`. Each environment defines its own
+/// payload type (usually an enum of everything that can happen in it) so
+/// that events can be dispatched without parsing.
+pub trait Event<P>: Display + Send {
     fn get_event_type(&self) -> &str;
     fn get_uid(&self) -> usize;
     fn get_time_stamp(&self) -> usize;
+    /// Move the event to a new timestamp. Used by the scheduler when
+    /// an event is rescheduled.
+    fn set_time_stamp(&mut self, timestamp: usize);
 
-    // Stringified JSON to use for arbitrary event handling
-    fn get_data(&self) -> Result<String, serde_json::Error>;
-}
+    /// Priority used to order events scheduled for the same timestamp.
+    /// Lower values are processed first. Events with equal timestamp and
+    /// priority are processed in the order they were scheduled.
+    fn get_priority(&self) -> i32 {
+        0
+    }
+
+    /// The typed payload handed to the environment when the event is applied.
+    fn get_payload(&self) -> &P;
 
-impl Ord for dyn Event {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.get_time_stamp().cmp(&other.get_time_stamp()).reverse()
+    // Stringified JSON of the payload, only used for display
+    #[cfg(feature = "json")]
+    fn get_data(&self) -> Result<String, serde_json::Error>
+    where
+        P: Serialize,
+    {
+        serde_json::to_string(self.get_payload())
+    }
+
+    /// The payload as shown when displaying the event, as JSON.
+    #[cfg(feature = "json")]
+    fn payload_text(&self) -> String
+    where
+        P: Serialize,
+    {
+        self.get_data().unwrap_or_default()
     }
-}
 
-impl PartialOrd for dyn Event {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    /// The payload as shown when displaying the event, in its debug form
+    /// without the `json` feature.
+    #[cfg(not(feature = "json"))]
+    fn payload_text(&self) -> String
+    where
+        P: Debug,
+    {
+        format!("{:?}", self.get_payload())
     }
 }
 
-impl PartialEq for dyn Event {
-    fn eq(&self, other: &Self) -> bool {
-        self.get_time_stamp() == other.get_time_stamp()
+/// A payload which knows how to rebuild the event that carried it.
+/// Used to restore pending events from a checkpoint.
+pub trait EventPayload: Sized {
+    fn into_event(self, uid: usize, timestamp: usize) -> Box<dyn Event<Self>>;
+}
+
+/// Serializable form of an event: enough to rebuild it through [EventPayload].
+#[derive(Deserialize, Serialize, Clone)]
+pub struct EventRecord<P> {
+    pub uid: usize,
+    pub timestamp: usize,
+    pub event_type: String,
+    pub payload: P,
+}
+
+impl<P: Clone> EventRecord<P> {
+    pub fn from_event(event: &dyn Event<P>) -> EventRecord<P> {
+        EventRecord {
+            uid: event.get_uid(),
+            timestamp: event.get_time_stamp(),
+            event_type: event.get_event_type().to_string(),
+            payload: event.get_payload().clone(),
+        }
     }
 }
 
-impl Eq for dyn Event {}
+impl<P: EventPayload> EventRecord<P> {
+    pub fn into_event(self) -> Box<dyn Event<P>> {
+        self.payload.into_event(self.uid, self.timestamp)
+    }
+}
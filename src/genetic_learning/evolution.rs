@@ -1,6 +1,8 @@
 use std::{fmt::Display, ops::Index};
 
-use rand::{seq::SliceRandom, thread_rng, Rng};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::rng::sim_rng::{seeded_rng, SimRng};
 
 /// Defines the genetic makeup of an individual
 /// Note: DNA can be represented in many ways, and so its represented
@@ -25,13 +27,14 @@ pub trait Fitness<F> {
 
 /// Defines how an individual can reproduce
 /// and mutate. Note: reproduce uses self and a second individual to
-/// create a new individual.
+/// create a new individual. All randomness must come from the provided rng
+/// so that evolution is reproducible for a given seed.
 pub trait Breedable<T>
 where
     Self: Sized,
 {
-    fn reproduce(&self, second: &Self) -> Result<Self, String>;
-    fn mutate(&mut self);
+    fn reproduce<R: Rng + ?Sized>(&self, second: &Self, rng: &mut R) -> Result<Self, String>;
+    fn mutate<R: Rng + ?Sized>(&mut self, rng: &mut R);
 }
 
 /// Population is a collection of individuals who can reproduce.
@@ -45,6 +48,7 @@ where
     T: Breedable<T>,
 {
     pub populace: Vec<T>,
+    rng: SimRng,
 }
 
 impl<T> Population<T>
@@ -54,9 +58,13 @@ where
     T: Display,
     T: Breedable<T>,
 {
-    /// Create a new population from a vector of individuals
-    pub fn new(pop: Vec<T>) -> Population<T> {
-        Population { populace: pop }
+    /// Create a new population from a vector of individuals.
+    /// The seed drives every random choice made while evolving.
+    pub fn new(pop: Vec<T>, seed: u64) -> Population<T> {
+        Population {
+            populace: pop,
+            rng: seeded_rng(seed),
+        }
     }
 
     /// get the number of members of the population
@@ -72,8 +80,13 @@ where
     /// Attempt to create a new individual from two parents
     /// Note: This is essential a wrapper for the [Breedable] trait of the individual,
     /// specifically the [Breedable::reproduce] function.
-    pub fn breed_from_parents(&self, first: &T, second: &T) -> Result<T, String> {
-        let child = first.reproduce(second);
+    pub fn breed_from_parents<R: Rng + ?Sized>(
+        &self,
+        first: &T,
+        second: &T,
+        rng: &mut R,
+    ) -> Result<T, String> {
+        let child = first.reproduce(second, rng);
         match child {
             Ok(child) => Ok(child),
             Err(e) => Err(e),
@@ -102,6 +115,44 @@ where
         }
     }
 
+    /// Breed the next generation from the elite of the current one.
+    fn next_generation(&self, rng: &mut SimRng) -> Result<Vec<T>, String> {
+        let mut new_pop: Vec<T> = Vec::new();
+
+        let mut elite = self.selection(10)?;
+
+        // if there are no elites, we need to find the best individual and call it the elite
+        if elite.is_empty() {
+            let mut max_fitness = 0.0;
+            let mut max_index = 0;
+            for (i, entity) in self.populace.iter().enumerate() {
+                let fitness = entity.evaluate_fitness();
+                if fitness >= max_fitness {
+                    max_fitness = fitness;
+                    max_index = i;
+                }
+            }
+            elite = vec![&self.populace[max_index]];
+        }
+
+        for _ in 0..self.len() {
+            let first = elite.choose(rng).unwrap();
+            let second = elite.choose(rng).unwrap();
+            // kinda gross we need to dereference once, but idk how to do it better
+            let child = self.breed_from_parents(*first, *second, rng);
+            match child {
+                Ok(mut child) => {
+                    if rng.gen_range(0..100) < 10 {
+                        child.mutate(rng);
+                    }
+                    new_pop.push(child);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(new_pop)
+    }
+
     /// Get the weights of the population. This is a vector of f32s
     /// that represent the fitness of each individual. This is used
     /// by the [Population::selection] function to select the top
@@ -159,41 +210,12 @@ where
     /// and mutate the population in place. This function will return an error if
     /// there are issues from the [Population::breed_from_parents] function.
     fn evolve(&mut self) -> Result<(), String> {
-        let mut new_pop: Vec<T> = Vec::new();
-        let rng = &mut thread_rng();
-
-        let mut elite = self.selection(10)?;
-
-        // if there are no elites, we need to find the best individual and call it the elite
-        if elite.is_empty() {
-            let mut max_fitness = 0.0;
-            let mut max_index = 0;
-            for (i, entity) in self.populace.iter().enumerate() {
-                let fitness = entity.evaluate_fitness();
-                if fitness >= max_fitness {
-                    max_fitness = fitness;
-                    max_index = i;
-                }
-            }
-            elite = vec![&self.populace[max_index]];
-        }
-
-        for _ in 0..self.len() {
-            let first = elite.choose(rng).unwrap();
-            let second = elite.choose(rng).unwrap();
-            // kinda gross we need to dereference once, but idk how to do it better
-            let child = self.breed_from_parents(*first, *second);
-            match child {
-                Ok(mut child) => {
-                    if rng.gen_range(0..100) < 10 {
-                        child.mutate();
-                    }
-                    new_pop.push(child);
-                }
-                Err(e) => return Err(e),
-            }
-        }
-        self.populace = new_pop;
+        // the rng is taken out for the duration of breeding, as the elite
+        // borrow the populace for the whole generation.
+        let mut rng = self.rng.clone();
+        let new_pop = self.next_generation(&mut rng);
+        self.rng = rng;
+        self.populace = new_pop?;
         Ok(())
     }
 
@@ -1,37 +1,63 @@
-mod statistics {
+pub mod statistics {
     pub mod data_point;
+    pub mod export;
     pub mod stats;
-    mod timeseries;
+    pub mod timeseries;
 }
 
 pub mod event {
+    #[allow(clippy::module_inception)]
     pub mod event;
 }
 
-mod des {
+pub mod des {
+    #[allow(clippy::module_inception)]
     pub mod des;
 }
 
+pub mod rng {
+    pub mod sim_rng;
+}
+
 pub mod simulation {
+    pub mod checkpoint;
+    pub mod movie;
+    pub mod observer;
+    pub mod replications;
     pub mod sim;
+    pub mod sim_error;
+    pub mod sweep;
+    #[cfg(test)]
+    pub(crate) mod test_world;
+    pub mod trace;
+    pub mod tui;
 }
 
 pub mod environment {
     pub mod bus_world {
         pub mod bus;
         pub mod bus_environment;
+        pub mod bus_scenario;
         pub mod bus_scenario_traits;
         pub mod bus_stop;
+        pub mod journey_planner;
         pub mod passenger;
+        pub mod passenger_arrivals;
+        pub mod route_network;
+        pub mod trip_records;
         pub mod bus_world_events {
+            pub mod bus_event;
             pub mod import_bus;
             pub mod load_passengers;
             pub mod move_bus_to_stop;
             pub mod new_bus;
-            pub mod unload_passengers;
+            pub mod passenger_abandon;
+            pub mod passenger_arrival;
             pub mod terminal_event;
+            pub mod unload_passengers;
         }
     }
+    #[allow(clippy::module_inception)]
     pub mod environment;
 }
 
@@ -1,101 +1,445 @@
+use std::error::Error;
+use std::fs::{self, File};
+#[cfg(feature = "json")]
+use std::io::BufReader;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use serde_json::json;
+
 use discrete_event_simulator::{
-    environment::bus_world::bus_environment::BusEnvironment,
-    environment::bus_world::bus_environment::BusEnvironmentSettings,
-    environment::bus_world::{
-        bus::Bus,
-        bus_world_events::{
-            import_bus::ImportBusEvent,
-            new_bus::{NewBusEvent, NewBusesJson},
-        },
-    },
-    genetic_learning::evolution::{Evolvable, Population},
+    environment::bus_world::bus::Bus,
+    environment::bus_world::bus_environment::WAIT_TIME,
+    environment::bus_world::bus_scenario::BusScenario,
+    environment::bus_world::bus_world_events::bus_event::BusEvent,
+    environment::bus_world::trip_records::TripRecorder,
+    simulation::replications::Replications,
     simulation::sim::Simulation,
+    simulation::sweep::{sweep_table, Grid, Sweep, SweepPoint},
+    simulation::tui::run_tui,
+    statistics::export::ExportFormat,
+    statistics::stats::Stats,
 };
+#[cfg(feature = "json")]
+use discrete_event_simulator::{
+    environment::bus_world::bus_environment::BusEnvironment,
+    simulation::trace::{replay, JsonLinesTrace},
+};
+
+/// Discrete event simulation of a bus world.
+///
+/// Exits with 0 on success, 1 if a scenario, simulation or replay fails
+/// and 2 on invalid arguments.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a scenario to the end and print or export its statistics.
+    Run {
+        /// Scenario file, .toml or .json
+        scenario: PathBuf,
+        /// Override the scenario's seed
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Leave everything recorded before this time out of summaries
+        #[arg(long)]
+        warm_up: Option<usize>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+        /// Write the statistics here instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// Record every event to this JSON Lines trace, for `replay`
+        #[arg(long)]
+        trace: Option<PathBuf>,
+        /// Write a record per completed passenger trip to this .csv,
+        /// .json or .jsonl file
+        #[arg(long)]
+        trips: Option<PathBuf>,
+    },
+    /// Animate a scenario in the terminal.
+    Movie {
+        scenario: PathBuf,
+        /// Milliseconds to wait between events
+        #[arg(long, default_value_t = 100)]
+        delay: u64,
+    },
+    /// Explore a scenario interactively: pause, step, change speed, jump
+    /// to a time and watch upcoming events and statistics.
+    Tui {
+        scenario: PathBuf,
+        /// Milliseconds to wait between events while running
+        #[arg(long, default_value_t = 100)]
+        delay: u64,
+        /// Statistic to chart, can be repeated; every level series by default
+        #[arg(long = "chart")]
+        charts: Vec<String>,
+    },
+    /// Evolve the routes of a scenario's fleet and compare the wait time
+    /// of the evolved fleet with the original one.
+    Evolve {
+        scenario: PathBuf,
+        #[arg(long, default_value_t = 20)]
+        generations: usize,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Run replications of every point of a parameter grid, see `SweepConfig`.
+    Sweep {
+        /// Sweep configuration, .toml or .json
+        config: PathBuf,
+        /// Worker threads, one per CPU by default
+        #[arg(long)]
+        threads: Option<usize>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Replay a trace recorded with `run --trace`, checking every state digest.
+    #[cfg(feature = "json")]
+    Replay {
+        trace: PathBuf,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human readable tables
+    Table,
+    Csv,
+    Json,
+    /// JSON Lines, one record per line
+    Jsonl,
+}
+
+/// A sweep: replications of a scenario at every combination of the
+/// parameter values in `grid`, see [BusScenario::with_parameter].
+///
+/// ```toml
+/// scenario = "demo.toml" # relative to this file
+/// replications = 5
+/// statistic = "Passenger Wait Time"
+///
+/// [grid]
+/// capacity = [5, 10]
+/// pickup_delay = [1, 2]
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SweepConfig {
+    scenario: PathBuf,
+    #[serde(default = "default_replications")]
+    replications: usize,
+    #[serde(default)]
+    base_seed: u64,
+    #[serde(default = "default_statistic")]
+    statistic: String,
+    grid: std::collections::BTreeMap<String, Vec<usize>>,
+}
+
+fn default_replications() -> usize {
+    5
+}
+
+fn default_statistic() -> String {
+    WAIT_TIME.to_string()
+}
 
-// fn main() {
-//     println!("Create a bus world simulation!");
-//     let mut env = BusEnvironment::new(BusEnvironmentSettings::default());
-//     let buses = NewBusesJson::new(5, 5);
-//     let init_event = Box::new(NewBusEvent::new(
-//         0,
-//         0,
-//         serde_json::to_string(&buses).unwrap(),
-//     ));
-//
-//     env.create_bus_stops(5);
-//     env.initialize_bus_stops_with_passengers(100);
-//
-//     let mut sim = Simulation::new(100, Box::new(env), init_event);
-//     sim.play_movie(100);
-//     // sim.run();
-// }
-
-fn main() {
-    println!("Create a bus world simulation!");
-    let mut buses = Vec::new();
-
-    let mut env = BusEnvironment::new(BusEnvironmentSettings::default());
-    env.create_bus_stops(5);
-    env.initialize_bus_stops_with_passengers(100);
-
-    for _ in 0..5 {
-        let mut bus = Bus::new(5);
-        for i in 0..5 {
-            bus.add_serviced_stop(env.bus_stops[i].name.clone());
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Run {
+            scenario,
+            seed,
+            warm_up,
+            format,
+            output,
+            trace,
+            trips,
+        } => run(&scenario, seed, warm_up, format, output, trace, trips),
+        Command::Movie { scenario, delay } => movie(&scenario, delay),
+        Command::Tui {
+            scenario,
+            delay,
+            charts,
+        } => tui(&scenario, delay, charts),
+        Command::Evolve {
+            scenario,
+            generations,
+            format,
+        } => evolve(&scenario, generations, format),
+        Command::Sweep {
+            config,
+            threads,
+            format,
+        } => sweep(&config, threads, format),
+        #[cfg(feature = "json")]
+        Command::Replay { trace, format } => replay_trace(&trace, format),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
         }
-        buses.push(bus);
     }
+}
 
-    let sim1_init_event = Box::new(ImportBusEvent::new(
-        0,
-        0,
-        serde_json::to_string(&buses).unwrap(),
-    ));
-    let mut sim = Simulation::new(100, Box::new(env), sim1_init_event);
-    // sim.play_movie(100);
-    sim.run();
-
-    let wait_time = match sim
-        .statistics
-        .get_series_by_name("Total Passenger Wait Time".to_string())
-    {
-        Some(series) => series,
-        None => panic!("No series found"),
-    };
+fn run(
+    scenario: &Path,
+    seed: Option<u64>,
+    warm_up: Option<usize>,
+    format: OutputFormat,
+    output: Option<PathBuf>,
+    trace: Option<PathBuf>,
+    trips: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let mut scenario = BusScenario::load(scenario)?;
+    if let Some(seed) = seed {
+        scenario = scenario.with_seed(seed);
+    }
+    let mut sim = scenario.build()?;
+    if let Some(warm_up) = warm_up {
+        sim.set_warm_up(warm_up);
+    }
+    if let Some(trace) = trace {
+        #[cfg(feature = "json")]
+        sim.record_trace(Box::new(JsonLinesTrace::create(trace, true)?))?;
+        #[cfg(not(feature = "json"))]
+        return Err(format!(
+            "cannot record the trace {}, traces need the json feature",
+            trace.display()
+        )
+        .into());
+    }
+    if let Some(trips) = trips {
+        sim.add_observer(Box::new(TripRecorder::create(trips)?));
+    }
+    sim.run()?;
+    sim.take_trace()?;
 
-    println!("{}", wait_time);
+    match output {
+        Some(path) => write_stats(&mut sim.statistics, format, File::create(path)?),
+        None => write_stats(&mut sim.statistics, format, io::stdout().lock()),
+    }
+}
 
-    let mut population = Population::new(buses.clone());
-    match population.evolve() {
-        Ok(_) => println!("Evolution successful!"),
-        Err(e) => println!("Evolution failed: {}", e),
+fn write_stats<W: Write>(
+    stats: &mut Stats,
+    format: OutputFormat,
+    mut writer: W,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Table => {
+            stats
+                .all_series
+                .sort_by(|a, b| a.statistic_label.cmp(&b.statistic_label));
+            for series in stats.all_series.iter() {
+                writeln!(writer, "{}", series)?;
+            }
+        }
+        OutputFormat::Csv => stats.export(&mut writer, ExportFormat::Csv)?,
+        OutputFormat::Json => {
+            stats.export(&mut writer, ExportFormat::Json)?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Jsonl => stats.export(&mut writer, ExportFormat::JsonLines)?,
     }
+    Ok(())
+}
+
+fn movie(scenario: &Path, delay: u64) -> Result<(), Box<dyn Error>> {
+    let mut sim = BusScenario::load(scenario)?.build()?;
+    sim.play_movie(delay)?;
+    Ok(())
+}
+
+fn tui(scenario: &Path, delay: u64, charts: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let mut sim = BusScenario::load(scenario)?.build()?;
+    run_tui(&mut sim, delay, charts)
+}
+
+fn mean_wait_time(sim: &Simulation<BusEvent>) -> Option<f64> {
+    let series = sim.statistics.get_series_by_name(WAIT_TIME.to_string())?;
+    series.mean()
+}
+
+fn evolve(scenario: &Path, generations: usize, format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let scenario = BusScenario::load(scenario)?;
 
-    println!("Evolving 20 times because reasons");
-    for _ in 0..20 {
-        let _ = population.evolve();
+    let (env, fleet) = scenario.prepare()?;
+    let evolved_fleet = scenario.evolve_fleet(&fleet, generations)?;
+    let mut original = scenario.assemble(env, fleet)?;
+    original.run()?;
+
+    let (env, _) = scenario.prepare()?;
+    let buses: Vec<Bus> = evolved_fleet.iter().map(|(_, bus)| bus.clone()).collect();
+    let mut evolved = scenario.assemble(env, evolved_fleet)?;
+    evolved.run()?;
+
+    let routes: Vec<&Vec<String>> = buses.iter().map(|bus| &bus.serviced_stop_names).collect();
+    let (original_wait, evolved_wait) = (mean_wait_time(&original), mean_wait_time(&evolved));
+    match format {
+        OutputFormat::Table => {
+            println!("Generations:                 {}", generations);
+            println!("Original mean wait time:     {:?}", original_wait);
+            println!("Evolved mean wait time:      {:?}", evolved_wait);
+            for (bus, route) in buses.iter().zip(routes) {
+                println!("{} {}", bus, route.join(" -> "));
+            }
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(io::stdout().lock());
+            writer.write_record(["generations", "original_wait", "evolved_wait"])?;
+            writer.write_record([
+                generations.to_string(),
+                original_wait.map(|w| w.to_string()).unwrap_or_default(),
+                evolved_wait.map(|w| w.to_string()).unwrap_or_default(),
+            ])?;
+            writer.flush()?;
+        }
+        OutputFormat::Json | OutputFormat::Jsonl => println!(
+            "{}",
+            json!({
+                "generations": generations,
+                "original_wait": original_wait,
+                "evolved_wait": evolved_wait,
+                "routes": routes,
+            })
+        ),
     }
+    Ok(())
+}
 
-    let mut env2 = BusEnvironment::new(BusEnvironmentSettings::default());
-    env2.create_bus_stops(5);
-    env2.initialize_bus_stops_with_passengers(100);
-
-    let sim2_init_event = Box::new(ImportBusEvent::new(
-        0,
-        0,
-        serde_json::to_string(&buses).unwrap(),
-    ));
-    let mut sim2 = Simulation::new(100, Box::new(env2), sim2_init_event);
-    // sim2.play_movie(100);
-    sim2.run();
-
-    let wait_time = match sim2
-        .statistics
-        .get_series_by_name("Total Passenger Wait Time".to_string())
-    {
-        Some(series) => series,
-        None => panic!("No series found"),
+fn sweep(
+    config: &Path,
+    threads: Option<usize>,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(config)?;
+    let sweep_config: SweepConfig = match config.extension().and_then(|e| e.to_str()) {
+        Some("json") => serde_json::from_str(&contents)?,
+        _ => toml::from_str(&contents)?,
     };
+    let scenario_path = config
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(&sweep_config.scenario);
+    let scenario = BusScenario::load(scenario_path)?;
+
+    let grid = sweep_config
+        .grid
+        .iter()
+        .fold(Grid::new(), |grid, (name, values)| grid.axis(name, values));
+    // check every parameter name before starting any work
+    let points = grid.points();
+    if let Some(point) = points.first() {
+        for name in point.0.keys() {
+            scenario.with_parameter(name, 0)?;
+        }
+    }
 
-    println!("{}", wait_time);
+    let replications = Replications::new(sweep_config.replications, sweep_config.base_seed);
+    let mut sweep = Sweep::new(points, replications);
+    if let Some(threads) = threads {
+        sweep.threads = threads;
+    }
+    let results = sweep.run(|point: &SweepPoint, seed| {
+        let mut configured = scenario.with_seed(seed);
+        for (name, value) in &point.0 {
+            configured = configured.with_parameter(name, *value)?;
+        }
+        configured.build()
+    })?;
+
+    let label = &sweep_config.statistic;
+    if format == OutputFormat::Table {
+        print!("{}", sweep_table(&results, label));
+        return Ok(());
+    }
+    let rows: Vec<serde_json::Value> = results
+        .iter()
+        .map(|result| {
+            let report = result.runs.report(label);
+            let interval = report.as_ref().and_then(|r| r.interval());
+            json!({
+                "parameters": result.point.0,
+                "statistic": label,
+                "replications": report.as_ref().map_or(0, |r| r.values.len()),
+                "missing": report.as_ref().map_or(0, |r| r.missing.len()),
+                "mean": report.as_ref().map(|r| r.mean),
+                "std_dev": report.as_ref().map(|r| r.std_dev),
+                "lower": interval.map(|(lower, _)| lower),
+                "upper": interval.map(|(_, upper)| upper),
+            })
+        })
+        .collect();
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::Value::Array(rows)),
+        OutputFormat::Jsonl => rows.iter().for_each(|row| println!("{}", row)),
+        _ => {
+            let mut writer = csv::Writer::from_writer(io::stdout().lock());
+            let names: Vec<&String> = sweep_config.grid.keys().collect();
+            let mut header: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+            header.extend([
+                "statistic",
+                "replications",
+                "missing",
+                "mean",
+                "std_dev",
+                "lower",
+                "upper",
+            ]);
+            writer.write_record(header)?;
+            for row in rows {
+                let mut record: Vec<String> = names
+                    .iter()
+                    .map(|name| row["parameters"][name.as_str()].to_string())
+                    .collect();
+                record.push(label.clone());
+                for field in [
+                    "replications",
+                    "missing",
+                    "mean",
+                    "std_dev",
+                    "lower",
+                    "upper",
+                ] {
+                    record.push(match &row[field] {
+                        serde_json::Value::Null => String::new(),
+                        value => value.to_string(),
+                    });
+                }
+                writer.write_record(record)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+fn replay_trace(trace: &Path, format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let mut env = BusEnvironment::default();
+    let mut replayed = replay(&mut env, BufReader::new(File::open(trace)?))?;
+    match format {
+        OutputFormat::Table => {
+            println!("Events replayed: {}", replayed.events_replayed);
+            println!("Digests checked: {}", replayed.digests_checked);
+        }
+        _ => write_stats(&mut replayed.statistics, format, io::stdout().lock())?,
+    }
+    if !replayed.verified() {
+        eprintln!("warning: the trace has no state digests, the replayed state was not checked");
+    }
+    Ok(())
 }
@@ -0,0 +1,41 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use uuid::Builder;
+
+/// The random number generator used throughout the simulation.
+/// Always construct it from a seed so a run can be reproduced exactly.
+pub type SimRng = ChaCha8Rng;
+
+/// Create a new [SimRng] from a seed.
+pub fn seeded_rng(seed: u64) -> SimRng {
+    SimRng::seed_from_u64(seed)
+}
+
+/// Generate a v4 uuid from the given rng instead of the OS entropy source,
+/// so that ids are reproducible for a given seed.
+pub fn new_uuid<R: Rng + ?Sized>(rng: &mut R) -> String {
+    Builder::from_random_bytes(rng.gen())
+        .into_uuid()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{new_uuid, seeded_rng};
+
+    #[test]
+    fn same_seed_same_uuids() {
+        let mut first = seeded_rng(42);
+        let mut second = seeded_rng(42);
+        for _ in 0..10 {
+            assert_eq!(new_uuid(&mut first), new_uuid(&mut second));
+        }
+    }
+
+    #[test]
+    fn different_seed_different_uuids() {
+        let mut first = seeded_rng(1);
+        let mut second = seeded_rng(2);
+        assert_ne!(new_uuid(&mut first), new_uuid(&mut second));
+    }
+}
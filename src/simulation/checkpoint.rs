@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::des::des::EventHandle;
+use crate::event::event::EventRecord;
+use crate::statistics::stats::Stats;
+
+/// A snapshot of a [Simulation] part way through a run. Restoring it with
+/// [Simulation::restore] continues the run exactly where it left off, so
+/// it can also be used to fork several what-if branches from one point.
+///
+/// The random number generator is owned by the environment, so its state
+/// is part of `environment_state`.
+///
+/// [Simulation]: crate::simulation::sim::Simulation
+/// [Simulation::restore]: crate::simulation::sim::Simulation::restore
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Checkpoint<P> {
+    pub current_time: usize,
+    pub runtime: usize,
+    pub next_sequence: u64,
+    pub event_count: usize,
+    pub finished: bool,
+    pub pending_events: Vec<(EventHandle, EventRecord<P>)>,
+    pub environment_state: String,
+    pub statistics: Stats,
+}
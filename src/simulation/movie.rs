@@ -0,0 +1,71 @@
+use std::io::Write;
+use std::time::Duration;
+use std::{io, thread};
+
+use crossterm::{cursor, execute, terminal};
+
+use crate::environment::environment::Environment;
+use crate::event::event::Event;
+use crate::simulation::observer::SimulationObserver;
+use crate::simulation::sim_error::SimError;
+use crate::statistics::stats::Stats;
+
+/// Animates a run in the terminal: the screen is redrawn with the
+/// environment before each event, and all statistics are printed at the end.
+pub struct MoviePlayer {
+    delay_millis: u64,
+}
+
+impl MoviePlayer {
+    pub fn new(delay_millis: u64) -> MoviePlayer {
+        MoviePlayer { delay_millis }
+    }
+}
+
+impl<P> SimulationObserver<P> for MoviePlayer {
+    fn before_event(
+        &mut self,
+        time: usize,
+        event: &dyn Event<P>,
+        environment: &dyn Environment<Payload = P>,
+        _statistics: &mut Stats,
+    ) -> Result<(), SimError> {
+        // Clear screen for animation
+        let _ = execute!(io::stdout(), terminal::Clear(terminal::ClearType::All));
+        // hide cursor
+        let _ = execute!(io::stdout(), cursor::Hide);
+
+        println!("Current Time: {}", time);
+        println!("Current Event: {}", event);
+        print!("\r{}", environment);
+        Ok(())
+    }
+
+    fn after_event(
+        &mut self,
+        _time: usize,
+        _event: &dyn Event<P>,
+        _environment: &dyn Environment<Payload = P>,
+        _statistics: &mut Stats,
+    ) -> Result<(), SimError> {
+        io::stdout().flush()?;
+        thread::sleep(Duration::from_millis(self.delay_millis));
+        Ok(())
+    }
+
+    fn on_terminate(
+        &mut self,
+        _time: usize,
+        _environment: &dyn Environment<Payload = P>,
+        statistics: &mut Stats,
+    ) -> Result<(), SimError> {
+        // Display statistics:
+        statistics
+            .all_series
+            .sort_by(|a, b| a.statistic_label.cmp(&b.statistic_label));
+        for series in statistics.all_series.iter() {
+            println!("{}", series);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use crate::environment::environment::Environment;
+use crate::event::event::Event;
+use crate::simulation::sim_error::SimError;
+use crate::statistics::stats::Stats;
+
+/// Hooks called by a [Simulation] around event processing, so that metrics,
+/// logging, visualisation and assertions can be plugged in without editing
+/// the environment. Every hook does nothing by default. Returning an error
+/// stops the run, with the error reported against the current event.
+///
+/// [Simulation]: crate::simulation::sim::Simulation
+pub trait SimulationObserver<P>: Send {
+    /// Called before an event is applied to the environment.
+    fn before_event(
+        &mut self,
+        _time: usize,
+        _event: &dyn Event<P>,
+        _environment: &dyn Environment<Payload = P>,
+        _statistics: &mut Stats,
+    ) -> Result<(), SimError> {
+        Ok(())
+    }
+
+    /// Called after an event has been applied to the environment.
+    fn after_event(
+        &mut self,
+        _time: usize,
+        _event: &dyn Event<P>,
+        _environment: &dyn Environment<Payload = P>,
+        _statistics: &mut Stats,
+    ) -> Result<(), SimError> {
+        Ok(())
+    }
+
+    /// Called for every event added to (or moved within) the scheduler.
+    fn on_schedule(&mut self, _time: usize, _event: &dyn Event<P>) -> Result<(), SimError> {
+        Ok(())
+    }
+
+    /// Called once the terminating event has been applied.
+    fn on_terminate(
+        &mut self,
+        _time: usize,
+        _environment: &dyn Environment<Payload = P>,
+        _statistics: &mut Stats,
+    ) -> Result<(), SimError> {
+        Ok(())
+    }
+}
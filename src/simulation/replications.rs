@@ -0,0 +1,297 @@
+use std::fmt::{self, Display, Formatter};
+
+use statrs::distribution::{ContinuousCDF, StudentsT};
+
+use crate::simulation::sim::Simulation;
+use crate::simulation::sim_error::{SimError, SimulationError};
+use crate::statistics::stats::Stats;
+use crate::statistics::timeseries::TimeSeries;
+
+/// Runs independent replications of a scenario, each with its own seed.
+pub struct Replications {
+    pub count: usize,
+    /// Replication `i` is seeded with `base_seed + i`, wrapping around
+    /// after `u64::MAX`.
+    pub base_seed: u64,
+    /// Confidence level of the reported intervals, e.g. 0.95.
+    pub confidence: f64,
+}
+
+/// The statistics of every replication, in seed order.
+pub struct ReplicationRuns {
+    pub seeds: Vec<u64>,
+    pub statistics: Vec<Stats>,
+    pub confidence: f64,
+}
+
+/// A statistic aggregated across replications: one value per replication,
+/// and a Student-t confidence interval for their mean.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplicationReport {
+    pub label: String,
+    pub unit: String,
+    /// The value of every replication that has one, with its seed.
+    pub values: Vec<(u64, f64)>,
+    /// Seeds of the replications without a value, e.g. because nobody
+    /// boarded a bus in them. They are not part of the mean or interval.
+    pub missing: Vec<u64>,
+    pub mean: f64,
+    /// Sample standard deviation across replications.
+    pub std_dev: f64,
+    pub confidence: f64,
+    /// Half the width of the confidence interval, `None` with fewer than
+    /// two replications.
+    pub half_width: Option<f64>,
+}
+
+impl Replications {
+    pub fn new(count: usize, base_seed: u64) -> Replications {
+        Replications {
+            count,
+            base_seed,
+            confidence: 0.95,
+        }
+    }
+
+    /// The seed of every replication, in order.
+    pub fn seeds(&self) -> Vec<u64> {
+        (0..self.count as u64)
+            .map(|i| self.base_seed.wrapping_add(i))
+            .collect()
+    }
+
+    /// Check the confidence level is strictly between 0 and 1.
+    pub fn validate(&self) -> Result<(), SimError> {
+        if self.confidence > 0.0 && self.confidence < 1.0 {
+            Ok(())
+        } else {
+            Err(SimError::InvalidScenario(format!(
+                "confidence level {} is not between 0 and 1",
+                self.confidence
+            )))
+        }
+    }
+
+    /// Build a simulation for every seed with `factory` and run it to the end.
+    pub fn run<P, F>(&self, mut factory: F) -> Result<ReplicationRuns, SimulationError>
+    where
+        F: FnMut(u64) -> Result<Simulation<P>, SimError>,
+    {
+        self.validate()
+            .map_err(|error| setup_error(error, "checking replications"))?;
+        let mut runs = ReplicationRuns {
+            seeds: Vec::new(),
+            statistics: Vec::new(),
+            confidence: self.confidence,
+        };
+        for seed in self.seeds() {
+            runs.statistics.push(self.run_one(seed, &mut factory)?);
+            runs.seeds.push(seed);
+        }
+        Ok(runs)
+    }
+
+    /// Build and run the replication with the given seed.
+    pub(crate) fn run_one<P, F>(&self, seed: u64, factory: F) -> Result<Stats, SimulationError>
+    where
+        F: FnOnce(u64) -> Result<Simulation<P>, SimError>,
+    {
+        let mut sim = factory(seed).map_err(|error| {
+            setup_error(error, &format!("building replication with seed {}", seed))
+        })?;
+        sim.run()?;
+        Ok(sim.statistics)
+    }
+}
+
+/// An error raised before any event was applied, while `doing` something.
+pub(crate) fn setup_error(error: SimError, doing: &str) -> SimulationError {
+    SimulationError {
+        error,
+        event_uid: 0,
+        event_type: String::new(),
+        event: doing.to_string(),
+        time: 0,
+        event_count: 0,
+    }
+}
+
+impl ReplicationRuns {
+    /// Aggregate the steady state mean of the named series.
+    pub fn report(&self, label: &str) -> Option<ReplicationReport> {
+        self.report_with(label, TimeSeries::mean)
+    }
+
+    /// Aggregate the named series, reducing it to one value per replication
+    /// with `measure`. Replications where the series is missing, or where
+    /// `measure` gives `None`, are reported as missing.
+    pub fn report_with<F>(&self, label: &str, mut measure: F) -> Option<ReplicationReport>
+    where
+        F: FnMut(&TimeSeries) -> Option<f64>,
+    {
+        let mut unit = None;
+        let mut values = Vec::new();
+        let mut missing = Vec::new();
+        for (seed, stats) in self.seeds.iter().zip(&self.statistics) {
+            let series = stats.get_series_by_name(label.to_string());
+            if let Some(series) = series {
+                unit.get_or_insert_with(|| series.unit.clone());
+            }
+            match series.and_then(&mut measure) {
+                Some(value) => values.push((*seed, value)),
+                None => missing.push(*seed),
+            }
+        }
+        ReplicationReport::new(label.to_string(), unit?, values, missing, self.confidence)
+    }
+}
+
+impl ReplicationReport {
+    pub fn new(
+        label: String,
+        unit: String,
+        values: Vec<(u64, f64)>,
+        missing: Vec<u64>,
+        confidence: f64,
+    ) -> Option<ReplicationReport> {
+        if values.is_empty() {
+            return None;
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().map(|(_, v)| v).sum::<f64>() / n;
+        let std_dev = if values.len() < 2 {
+            0.0
+        } else {
+            (values.iter().map(|(_, v)| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        };
+        let half_width = StudentsT::new(0.0, 1.0, n - 1.0)
+            .ok()
+            .map(|t| t.inverse_cdf(0.5 + confidence / 2.0) * std_dev / n.sqrt());
+        Some(ReplicationReport {
+            label,
+            unit,
+            values,
+            missing,
+            mean,
+            std_dev,
+            confidence,
+            half_width,
+        })
+    }
+
+    /// The confidence interval for the mean, `None` with fewer than two replications.
+    pub fn interval(&self) -> Option<(f64, f64)> {
+        let half_width = self.half_width?;
+        Some((self.mean - half_width, self.mean + half_width))
+    }
+}
+
+impl Display for ReplicationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut output = String::new();
+        let underline = "=".repeat(self.label.chars().count());
+        output.push_str(&format!("{}\n{}\n{}\n", underline, self.label, underline));
+        output.push_str(&format!("{:<11} | {}\n", "Seed", self.unit));
+        for (seed, value) in &self.values {
+            output.push_str(&format!("{:<11} | {}\n", seed, value));
+        }
+        for seed in &self.missing {
+            output.push_str(&format!("{:<11} | missing\n", seed));
+        }
+        output.push_str(&format!("{:<11} | {}\n", "Mean", self.mean));
+        output.push_str(&format!("{:<11} | {}\n", "Std Dev", self.std_dev));
+        if let Some((lower, upper)) = self.interval() {
+            output.push_str(&format!(
+                "{:<11} | {} to {}\n",
+                format!("{}% CI", self.confidence * 100.0),
+                lower,
+                upper
+            ));
+        }
+        write!(f, "{}", output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReplicationReport, Replications};
+    use crate::environment::bus_world::bus_world_events::bus_event::BusEvent;
+    use crate::simulation::sim::Simulation;
+    use crate::simulation::sim_error::SimError;
+    use crate::simulation::test_world::TestWorld;
+
+    fn scenario(seed: u64) -> Result<Simulation<BusEvent>, SimError> {
+        TestWorld {
+            seed,
+            ..TestWorld::default()
+        }
+        .build()
+    }
+
+    #[test]
+    fn interval_matches_student_t() {
+        let report = ReplicationReport::new(
+            "test".to_string(),
+            "unit".to_string(),
+            vec![(0, 1.0), (1, 2.0), (2, 3.0), (3, 4.0), (4, 5.0)],
+            Vec::new(),
+            0.95,
+        )
+        .unwrap();
+        assert_eq!(report.mean, 3.0);
+        assert!((report.std_dev - 2.5f64.sqrt()).abs() < 1e-12);
+        // t(0.975, 4) = 2.776445
+        let expected = 2.776445 * 2.5f64.sqrt() / 5f64.sqrt();
+        assert!((report.half_width.unwrap() - expected).abs() < 1e-5);
+
+        let single =
+            ReplicationReport::new("test".into(), "unit".into(), vec![(0, 1.0)], vec![1], 0.95)
+                .unwrap();
+        assert_eq!(single.interval(), None);
+        assert!(single.to_string().contains("1           | missing"));
+    }
+
+    #[test]
+    fn seeds_wrap_and_confidence_is_checked() {
+        let replications = Replications::new(3, u64::MAX - 1);
+        assert_eq!(replications.seeds(), vec![u64::MAX - 1, u64::MAX, 0]);
+
+        for confidence in [0.0, 1.0, 1.5, f64::NAN] {
+            let replications = Replications {
+                confidence,
+                ..Replications::new(2, 0)
+            };
+            let failure = replications.run(scenario).err().unwrap();
+            assert!(matches!(failure.error, SimError::InvalidScenario(_)));
+        }
+    }
+
+    #[test]
+    fn replications_use_distinct_seeds() {
+        let runs = Replications::new(4, 10).run(scenario).unwrap();
+        assert_eq!(runs.seeds, vec![10, 11, 12, 13]);
+
+        let report = runs.report("Passenger Wait Time").unwrap();
+        let seeds: Vec<u64> = report.values.iter().map(|(seed, _)| *seed).collect();
+        assert_eq!(seeds, runs.seeds);
+        assert!(report.missing.is_empty());
+        assert!(report.values.iter().any(|(_, v)| *v != report.values[0].1));
+        let (lower, upper) = report.interval().unwrap();
+        assert!(lower <= report.mean && report.mean <= upper);
+        assert!(format!("{}", report).contains("95% CI"));
+
+        // the same seeds reproduce the same report
+        let again = Replications::new(4, 10).run(scenario).unwrap();
+        assert_eq!(again.report("Passenger Wait Time").as_ref(), Some(&report));
+        assert!(runs.report("no such statistic").is_none());
+
+        // a replication without a value is reported rather than dropped
+        let partial = runs
+            .report_with("Passenger Wait Time", |series| {
+                Some(series.mean()?).filter(|mean| *mean != report.values[1].1)
+            })
+            .unwrap();
+        assert_eq!(partial.values.len(), 3);
+        assert_eq!(partial.missing, vec![11]);
+    }
+}
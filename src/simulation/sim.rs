@@ -1,96 +1,345 @@
-use std::io::Write;
-use std::time::Duration;
-use std::{io, thread};
-
-use crossterm::{cursor, execute, terminal};
-
-use crate::des::des::Scheduler;
+use crate::des::des::{EventHandle, Scheduler};
 use crate::environment::environment::Environment;
-use crate::event::event::Event;
+use crate::event::event::{Event, EventPayload, EventRecord};
+use crate::simulation::checkpoint::Checkpoint;
+use crate::simulation::movie::MoviePlayer;
+use crate::simulation::observer::SimulationObserver;
+use crate::simulation::sim_error::{SimError, SimulationError};
+use crate::simulation::trace::TraceSink;
 use crate::statistics::data_point::DataPoint;
 use crate::statistics::stats::Stats;
 
-pub struct Simulation {
-    scheduler: Scheduler,
-    pub environment: Box<dyn Environment>,
+pub struct Simulation<P> {
+    scheduler: Scheduler<P>,
+    pub environment: Box<dyn Environment<Payload = P>>,
     pub statistics: Stats,
+    event_count: usize,
+    finished: bool,
+    trace: Option<Box<dyn TraceSink<P>>>,
+    observers: Vec<Box<dyn SimulationObserver<P>>>,
 }
 
-impl Simulation {
+impl<P> Simulation<P> {
     pub fn new(
         runtime: usize,
-        environment: Box<dyn Environment>,
-        initial_event: Box<dyn Event>,
+        environment: Box<dyn Environment<Payload = P>>,
+        initial_event: Box<dyn Event<P>>,
     ) -> Self {
         let mut sim = Simulation {
             scheduler: Scheduler::new(runtime),
             environment,
             statistics: Stats::new(),
+            event_count: 0,
+            finished: false,
+            trace: None,
+            observers: Vec::new(),
         };
         sim.scheduler.add_event(initial_event);
-        return sim;
+        sim
     }
 
-    fn terminal_event(&mut self) {
-        self.scheduler
-            .add_event(self.environment.terminating_event());
-        let last_event = self.scheduler.next_event().unwrap();
+    /// The current simulated time.
+    pub fn current_time(&self) -> usize {
+        self.scheduler.current_time
+    }
+
+    /// The time after which no more events are applied.
+    pub fn runtime(&self) -> usize {
+        self.scheduler.runtime
+    }
+
+    /// Every event still waiting to be applied, in the order they will be.
+    pub fn upcoming_events(&self) -> Vec<(EventHandle, &dyn Event<P>)> {
+        self.scheduler.pending_events()
+    }
+
+    /// The number of events applied so far, not counting the terminating event.
+    pub fn event_count(&self) -> usize {
+        self.event_count
+    }
+
+    /// Whether [Simulation::finish] has been called.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Treat everything recorded before `period` as warm-up, so that
+    /// [TimeSeries] summaries only describe steady-state behaviour.
+    ///
+    /// [TimeSeries]: crate::statistics::timeseries::TimeSeries
+    pub fn set_warm_up(&mut self, period: usize) {
+        self.statistics.set_warm_up_end(period);
+    }
+
+    /// Run up to the end of the warm-up period and then discard every
+    /// statistic recorded so far.
+    pub fn run_warm_up(&mut self, period: usize) -> Result<usize, SimulationError> {
+        self.set_warm_up(period);
+        let applied = self.run_until(period)?;
+        self.statistics.reset();
+        Ok(applied)
+    }
+
+    /// Record every event applied from now on to the given sink. The sink
+    /// first receives the current state of the environment, so the trace
+    /// can be replayed without rebuilding the scenario.
+    pub fn record_trace(&mut self, mut sink: Box<dyn TraceSink<P>>) -> Result<(), SimError> {
+        sink.start(
+            self.scheduler.current_time,
+            self.scheduler.next_sequence(),
+            self.environment.as_ref(),
+        )?;
+        self.trace = Some(sink);
+        Ok(())
+    }
+
+    /// Stop recording and hand back the trace sink, flushed.
+    pub fn take_trace(&mut self) -> Result<Option<Box<dyn TraceSink<P>>>, SimError> {
+        if let Some(sink) = self.trace.as_mut() {
+            sink.flush()?;
+        }
+        Ok(self.trace.take())
+    }
+
+    /// Register an observer, called around every event from now on.
+    pub fn add_observer(&mut self, observer: Box<dyn SimulationObserver<P>>) {
+        self.observers.push(observer);
+    }
+
+    /// Remove and return the most recently registered observer.
+    pub fn pop_observer(&mut self) -> Option<Box<dyn SimulationObserver<P>>> {
+        self.observers.pop()
+    }
+
+    /// Tell the observers about everything scheduled since the last call.
+    fn notify_scheduled(&mut self) -> Result<(), SimError> {
+        for handle in self.scheduler.take_recently_scheduled() {
+            if let Some(event) = self.scheduler.get_event(handle) {
+                for observer in self.observers.iter_mut() {
+                    observer.on_schedule(self.scheduler.current_time, event)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_and_notify(&mut self, event: &dyn Event<P>) -> Result<(), SimError> {
+        let time = self.scheduler.current_time;
+        for observer in self.observers.iter_mut() {
+            observer.before_event(time, event, self.environment.as_ref(), &mut self.statistics)?;
+        }
         self.environment
-            .apply_event(&mut self.scheduler, &mut self.statistics, last_event);
+            .apply_event(&mut self.scheduler, &mut self.statistics, event)?;
+        if let Some(sink) = self.trace.as_mut() {
+            sink.record(time, event, self.environment.as_ref())?;
+        }
+        self.notify_scheduled()?;
+        for observer in self.observers.iter_mut() {
+            observer.after_event(time, event, self.environment.as_ref(), &mut self.statistics)?;
+        }
+        Ok(())
     }
 
-    pub fn run(&mut self) {
-        let mut event_count = 0;
-        while let Some(event) = self.scheduler.next_event() {
-            self.environment
-                .apply_event(&mut self.scheduler, &mut self.statistics, event);
-            event_count += 1;
+    /// Apply a single event to the environment, attaching the event and
+    /// the current position in the run to any error it raises.
+    fn apply(&mut self, event: &dyn Event<P>) -> Result<(), SimulationError> {
+        self.apply_and_notify(event).map_err(|error| {
+            SimulationError::new(error, event, self.scheduler.current_time, self.event_count)
+        })
+    }
+
+    /// Apply exactly one event and return it. Returns `None` once there are
+    /// no events left before the runtime, or the simulation has finished.
+    pub fn step(&mut self) -> Result<Option<Box<dyn Event<P>>>, SimulationError> {
+        if self.finished {
+            return Ok(None);
+        }
+        match self.scheduler.next_event() {
+            Some(event) => {
+                self.apply(event.as_ref())?;
+                self.event_count += 1;
+                Ok(Some(event))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Apply events for as long as the predicate holds. The predicate is
+    /// checked before every event. Returns the number of events applied.
+    pub fn run_while<F>(&mut self, mut predicate: F) -> Result<usize, SimulationError>
+    where
+        F: FnMut(&dyn Environment<Payload = P>, &Stats) -> bool,
+    {
+        let mut applied = 0;
+        while predicate(self.environment.as_ref(), &self.statistics) {
+            if self.step()?.is_none() {
+                break;
+            }
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    /// Apply every event up to and including `time`, then move the clock
+    /// forward to `time` (capped at the runtime). Returns the number of events applied.
+    pub fn run_until(&mut self, time: usize) -> Result<usize, SimulationError> {
+        let mut applied = 0;
+        while !self.finished && self.scheduler.peek_time().is_some_and(|next| next <= time) {
+            if self.step()?.is_none() {
+                break;
+            }
+            applied += 1;
+        }
+        let target = time.min(self.scheduler.runtime);
+        if !self.finished && target > self.scheduler.current_time {
+            self.scheduler.current_time = target;
+        }
+        Ok(applied)
+    }
+
+    /// Apply at most `n_events` events. Returns the number of events applied.
+    pub fn run_for(&mut self, n_events: usize) -> Result<usize, SimulationError> {
+        let mut applied = 0;
+        while applied < n_events && self.step()?.is_some() {
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    /// Apply the environment's terminating event at the current time and
+    /// record the number of events ran. Further steps do nothing.
+    pub fn finish(&mut self) -> Result<(), SimulationError> {
+        if self.finished {
+            return Ok(());
+        }
+        let mut last_event = self.environment.terminating_event();
+        last_event.set_time_stamp(self.scheduler.current_time);
+        self.apply(last_event.as_ref())?;
+        self.finished = true;
+        if let Some(sink) = self.trace.as_mut() {
+            sink.flush().map_err(|e| {
+                SimulationError::new(
+                    e.into(),
+                    last_event.as_ref(),
+                    self.scheduler.current_time,
+                    self.event_count,
+                )
+            })?;
         }
-        // Apply the terminating event
-        self.terminal_event();
 
         let data_point = DataPoint::new(
             self.scheduler.current_time,
-            event_count as f64,
+            self.event_count as f64,
             "Count".to_string(),
         );
         self.statistics
-            .add_statistic(data_point, "Events Ran".to_string())
+            .add_statistic(data_point, "Events Ran".to_string());
+
+        for observer in self.observers.iter_mut() {
+            observer
+                .on_terminate(
+                    self.scheduler.current_time,
+                    self.environment.as_ref(),
+                    &mut self.statistics,
+                )
+                .map_err(|e| {
+                    SimulationError::new(
+                        e,
+                        last_event.as_ref(),
+                        self.scheduler.current_time,
+                        self.event_count,
+                    )
+                })?;
+        }
+        Ok(())
     }
 
-    pub fn play_movie(&mut self, delay_millis: u64) {
-        while let Some(event) = self.scheduler.next_event() {
-            // Clear screen for animation
-            let _ = execute!(io::stdout(), terminal::Clear(terminal::ClearType::All));
-            // hide cursor
-            let _ = execute!(io::stdout(), cursor::Hide);
+    pub fn run(&mut self) -> Result<(), SimulationError> {
+        while self.step()?.is_some() {}
+        // Apply the terminating event
+        self.finish()
+    }
 
-            println!("Current Time: {}", self.scheduler.current_time);
-            println!("Current Event: {}", event);
-            print!("\r{}", self.environment);
+    /// Run the simulation, animating it in the terminal with a [MoviePlayer].
+    pub fn play_movie(&mut self, delay_millis: u64) -> Result<(), SimulationError> {
+        self.add_observer(Box::new(MoviePlayer::new(delay_millis)));
+        let result = self.run();
+        self.pop_observer();
+        result
+    }
 
-            // apply the event
-            self.environment
-                .apply_event(&mut self.scheduler, &mut self.statistics, event);
+    pub fn add_arbitrary_event(
+        &mut self,
+        from: Box<dyn Event<P>>,
+    ) -> Result<EventHandle, SimError> {
+        let handle = self.scheduler.add_event(from);
+        self.notify_scheduled()?;
+        Ok(handle)
+    }
 
-            io::stdout().flush().unwrap();
+    pub fn cancel_event(&mut self, handle: EventHandle) -> Option<Box<dyn Event<P>>> {
+        self.scheduler.cancel(handle)
+    }
 
-            thread::sleep(Duration::from_millis(delay_millis));
-        }
-        // Apply the terminating event
-        self.terminal_event();
+    pub fn reschedule_event(
+        &mut self,
+        handle: EventHandle,
+        new_time: usize,
+    ) -> Result<(), SimError> {
+        self.scheduler
+            .reschedule(handle, new_time)
+            .map_err(SimError::InvalidState)?;
+        self.notify_scheduled()
+    }
+}
 
-        // Display statistics:
-        self.statistics
-            .all_series
-            .sort_by(|a, b| a.statistic_label.cmp(&b.statistic_label));
-        for series in self.statistics.all_series.iter() {
-            println!("{}", series);
+impl<P: EventPayload + Clone> Simulation<P> {
+    /// Snapshot the scheduler clock, pending events, environment state
+    /// and statistics.
+    pub fn checkpoint(&self) -> Checkpoint<P> {
+        Checkpoint {
+            current_time: self.scheduler.current_time,
+            runtime: self.scheduler.runtime,
+            next_sequence: self.scheduler.next_sequence(),
+            event_count: self.event_count,
+            finished: self.finished,
+            pending_events: self
+                .scheduler
+                .pending_events()
+                .into_iter()
+                .map(|(handle, event)| (handle, EventRecord::from_event(event)))
+                .collect(),
+            environment_state: self.environment.get_state(),
+            statistics: self.statistics.clone(),
         }
     }
 
-    pub fn add_arbitrary_event(&mut self, from: Box<dyn Event>) {
-        self.scheduler.add_event(from);
+    /// Build a simulation from a checkpoint. The given environment is
+    /// overwritten with the checkpointed state.
+    pub fn restore(
+        mut environment: Box<dyn Environment<Payload = P>>,
+        checkpoint: &Checkpoint<P>,
+    ) -> Result<Self, SimError> {
+        environment.restore_state(&checkpoint.environment_state)?;
+        let events = checkpoint
+            .pending_events
+            .iter()
+            .map(|(handle, record)| (*handle, record.clone().into_event()))
+            .collect();
+        Ok(Simulation {
+            scheduler: Scheduler::restore(
+                checkpoint.runtime,
+                checkpoint.current_time,
+                checkpoint.next_sequence,
+                events,
+            ),
+            environment,
+            statistics: checkpoint.statistics.clone(),
+            event_count: checkpoint.event_count,
+            finished: checkpoint.finished,
+            trace: None,
+            observers: Vec::new(),
+        })
     }
 }
 
@@ -98,22 +347,283 @@ impl Simulation {
 mod test {
 
     use crate::environment::bus_world::bus_environment::{BusEnvironment, BusEnvironmentSettings};
+    use crate::environment::bus_world::bus_world_events::bus_event::BusEvent;
     use crate::environment::bus_world::bus_world_events::new_bus::{NewBusEvent, NewBusesJson};
+    use crate::environment::environment::Environment;
+    use crate::event::event::Event;
+    use crate::simulation::checkpoint::Checkpoint;
+    use crate::simulation::observer::SimulationObserver;
     use crate::simulation::sim::Simulation;
+    use crate::simulation::sim_error::SimError;
+    use crate::simulation::test_world::TestWorld;
+    use crate::statistics::stats::Stats;
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn simulation_run() {
         let number_of_buses = NewBusesJson::new(10, 15);
-        let initial_event = Box::new(NewBusEvent::new(
-            1,
-            0,
-            serde_json::to_string(&number_of_buses).unwrap(),
-        ));
+        let initial_event = Box::new(NewBusEvent::new(1, 0, number_of_buses));
         let settings = BusEnvironmentSettings::default();
         let mut env = BusEnvironment::new(settings);
         env.create_bus_stops(3);
         let mut simulation = Simulation::new(100, Box::new(env), initial_event);
-        simulation.run();
+        simulation.run().unwrap();
         assert_eq!(1, 1);
     }
+
+    #[test]
+    fn run_surfaces_failing_event() {
+        let initial_event = Box::new(NewBusEvent::new(1, 3, NewBusesJson::new(1, 5)));
+        // no bus stops, so the buses have nowhere to go
+        let env = BusEnvironment::new(BusEnvironmentSettings::default());
+        let mut simulation = Simulation::new(100, Box::new(env), initial_event);
+        let failure = simulation.run().unwrap_err();
+        assert!(matches!(failure.error, SimError::InvalidState(_)));
+        assert_eq!(failure.event_type, "NewBus");
+        assert_eq!(failure.event_uid, 1);
+        assert_eq!(failure.time, 3);
+        assert_eq!(failure.event_count, 0);
+    }
+
+    fn seeded_simulation(seed: u64) -> Simulation<BusEvent> {
+        TestWorld {
+            seed,
+            stops: 5,
+            passengers: 50,
+            buses: 3,
+            ..TestWorld::default()
+        }
+        .build()
+        .unwrap()
+    }
+
+    #[test]
+    fn same_seed_reproduces_run() {
+        let mut first = seeded_simulation(7);
+        let mut second = seeded_simulation(7);
+        assert_eq!(
+            first.environment.get_state(),
+            second.environment.get_state()
+        );
+        first.run().unwrap();
+        second.run().unwrap();
+        assert_eq!(
+            first.environment.get_state(),
+            second.environment.get_state()
+        );
+    }
+
+    #[test]
+    fn step_applies_one_event() {
+        let mut simulation = seeded_simulation(1);
+        let first = simulation.step().unwrap().unwrap();
+        assert_eq!(first.get_event_type(), "NewBus");
+        assert_eq!(simulation.event_count(), 1);
+        let second = simulation.step().unwrap().unwrap();
+        assert_eq!(second.get_event_type(), "UnloadPassengers");
+        assert_eq!(simulation.current_time(), 10);
+    }
+
+    #[test]
+    fn run_for_and_run_until() {
+        let mut simulation = seeded_simulation(1);
+        assert_eq!(simulation.run_for(5).unwrap(), 5);
+        assert_eq!(simulation.event_count(), 5);
+
+        simulation.run_until(50).unwrap();
+        assert_eq!(simulation.current_time(), 50);
+        assert!(simulation.scheduler.peek_time().unwrap() > 50);
+
+        // running past the runtime stops at the runtime
+        simulation.run_until(1000).unwrap();
+        assert_eq!(simulation.current_time(), 100);
+        assert!(simulation.step().unwrap().is_none());
+    }
+
+    #[test]
+    fn run_while_sees_statistics() {
+        let mut simulation = seeded_simulation(1);
+        simulation
+            .run_while(|_, stats| {
+                stats
+                    .get_series_by_name("stop B: buses".to_string())
+                    .is_none()
+            })
+            .unwrap();
+        assert!(simulation
+            .statistics
+            .get_series_by_name("stop B: buses".to_string())
+            .is_some());
+        assert!(!simulation.is_finished());
+    }
+
+    #[test]
+    fn stepping_matches_run() {
+        let mut stepped = seeded_simulation(3);
+        let mut ran = seeded_simulation(3);
+        while stepped.step().unwrap().is_some() {}
+        stepped.finish().unwrap();
+        ran.run().unwrap();
+        assert_eq!(stepped.event_count(), ran.event_count());
+        assert_eq!(stepped.environment.get_state(), ran.environment.get_state());
+        assert!(stepped.step().unwrap().is_none());
+    }
+
+    #[test]
+    fn restored_checkpoint_matches_uninterrupted_run() {
+        let mut uninterrupted = seeded_simulation(11);
+        uninterrupted.run().unwrap();
+
+        let mut interrupted = seeded_simulation(11);
+        interrupted.run_until(40).unwrap();
+        let serialized = serde_json::to_string(&interrupted.checkpoint()).unwrap();
+        let checkpoint: Checkpoint<BusEvent> = serde_json::from_str(&serialized).unwrap();
+
+        let mut restored =
+            Simulation::restore(Box::new(BusEnvironment::default()), &checkpoint).unwrap();
+        assert_eq!(restored.current_time(), 40);
+        restored.run().unwrap();
+        interrupted.run().unwrap();
+
+        assert_eq!(restored.statistics, uninterrupted.statistics);
+        assert_eq!(interrupted.statistics, uninterrupted.statistics);
+        assert_eq!(
+            restored.environment.get_state(),
+            uninterrupted.environment.get_state()
+        );
+    }
+
+    #[test]
+    fn different_seed_changes_run() {
+        let first = seeded_simulation(7);
+        let second = seeded_simulation(8);
+        assert_ne!(
+            first.environment.get_state(),
+            second.environment.get_state()
+        );
+    }
+
+    #[derive(Default)]
+    struct Counts {
+        before: usize,
+        after: usize,
+        scheduled: usize,
+        terminated: usize,
+    }
+
+    struct CountingObserver {
+        counts: Arc<Mutex<Counts>>,
+        fail_after: Option<usize>,
+    }
+
+    impl SimulationObserver<BusEvent> for CountingObserver {
+        fn before_event(
+            &mut self,
+            _time: usize,
+            _event: &dyn Event<BusEvent>,
+            _environment: &dyn Environment<Payload = BusEvent>,
+            _statistics: &mut Stats,
+        ) -> Result<(), SimError> {
+            self.counts.lock().unwrap().before += 1;
+            Ok(())
+        }
+
+        fn after_event(
+            &mut self,
+            _time: usize,
+            _event: &dyn Event<BusEvent>,
+            _environment: &dyn Environment<Payload = BusEvent>,
+            _statistics: &mut Stats,
+        ) -> Result<(), SimError> {
+            let mut counts = self.counts.lock().unwrap();
+            counts.after += 1;
+            match self.fail_after {
+                Some(limit) if counts.after >= limit => Err(SimError::InvalidState(
+                    "observer stopped the run".to_string(),
+                )),
+                _ => Ok(()),
+            }
+        }
+
+        fn on_schedule(
+            &mut self,
+            _time: usize,
+            _event: &dyn Event<BusEvent>,
+        ) -> Result<(), SimError> {
+            self.counts.lock().unwrap().scheduled += 1;
+            Ok(())
+        }
+
+        fn on_terminate(
+            &mut self,
+            _time: usize,
+            _environment: &dyn Environment<Payload = BusEvent>,
+            _statistics: &mut Stats,
+        ) -> Result<(), SimError> {
+            self.counts.lock().unwrap().terminated += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn observers_see_every_event() {
+        let counts = Arc::new(Mutex::new(Counts::default()));
+        let mut sim = seeded_simulation(3);
+        sim.add_observer(Box::new(CountingObserver {
+            counts: counts.clone(),
+            fail_after: None,
+        }));
+        sim.run().unwrap();
+
+        let counts = counts.lock().unwrap();
+        // every event plus the terminating event
+        assert_eq!(counts.before, sim.event_count() + 1);
+        assert_eq!(counts.after, counts.before);
+        assert!(counts.scheduled > 0);
+        assert_eq!(counts.terminated, 1);
+    }
+
+    #[test]
+    fn observer_error_stops_run() {
+        let counts = Arc::new(Mutex::new(Counts::default()));
+        let mut sim = seeded_simulation(3);
+        sim.add_observer(Box::new(CountingObserver {
+            counts: counts.clone(),
+            fail_after: Some(3),
+        }));
+        let failure = sim.run().unwrap_err();
+        assert!(matches!(failure.error, SimError::InvalidState(_)));
+        assert_eq!(failure.event_count, 2);
+        assert_eq!(counts.lock().unwrap().terminated, 0);
+    }
+
+    #[test]
+    fn warm_up_is_left_out_of_summaries() {
+        let mut tagged = seeded_simulation(5);
+        tagged.set_warm_up(30);
+        tagged.run().unwrap();
+        let mut plain = seeded_simulation(5);
+        plain.run().unwrap();
+
+        let label = "stop B: buses".to_string();
+        let tagged_series = tagged.statistics.get_series_by_name(label.clone()).unwrap();
+        let plain_series = plain.statistics.get_series_by_name(label).unwrap();
+        assert_eq!(tagged_series.series, plain_series.series);
+        assert!(tagged_series.steady_state().all(|(time, _)| time >= 30));
+        assert_eq!(
+            tagged_series.warm_up().count() + tagged_series.steady_state().count(),
+            plain_series.observations().count()
+        );
+    }
+
+    #[test]
+    fn run_warm_up_resets_statistics() {
+        let mut sim = seeded_simulation(5);
+        sim.run_warm_up(30).unwrap();
+        assert!(sim.statistics.all_series.is_empty());
+        sim.run().unwrap();
+        for series in sim.statistics.all_series.iter() {
+            assert!(series.series.keys().all(|time| *time >= 30));
+        }
+    }
 }
@@ -0,0 +1,113 @@
+use std::fmt::{Display, Error, Formatter};
+use std::io;
+
+use crate::event::event::Event;
+
+/// Errors raised while applying an event, by the [Environment] itself or
+/// by the simulation around it (e.g. writing a trace).
+///
+/// [Environment]: crate::environment::environment::Environment
+#[derive(Debug)]
+pub enum SimError {
+    /// The environment does not know how to handle this kind of event.
+    UnknownEvent(String),
+    /// An entity referenced by an event (a bus, a stop, ...) does not exist.
+    MissingEntity { kind: &'static str, id: String },
+    /// An event payload could not be decoded.
+    PayloadDecode(serde_json::Error),
+    /// The environment is not in a state where the event can be applied.
+    InvalidState(String),
+    /// Reading or writing a trace failed.
+    Io(io::Error),
+    /// A scenario description could not be parsed or does not make sense.
+    InvalidScenario(String),
+}
+
+impl SimError {
+    pub fn missing(kind: &'static str, id: impl Into<String>) -> SimError {
+        SimError::MissingEntity {
+            kind,
+            id: id.into(),
+        }
+    }
+}
+
+impl Display for SimError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            SimError::UnknownEvent(event_type) => write!(f, "Unknown event type {}", event_type),
+            SimError::MissingEntity { kind, id } => write!(f, "{} {} not found", kind, id),
+            SimError::PayloadDecode(e) => write!(f, "Could not decode event payload: {}", e),
+            SimError::InvalidState(reason) => write!(f, "Invalid state: {}", reason),
+            SimError::Io(e) => write!(f, "IO error: {}", e),
+            SimError::InvalidScenario(reason) => write!(f, "Invalid scenario: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SimError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SimError::PayloadDecode(e) => Some(e),
+            SimError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SimError {
+    fn from(e: serde_json::Error) -> Self {
+        SimError::PayloadDecode(e)
+    }
+}
+
+impl From<io::Error> for SimError {
+    fn from(e: io::Error) -> Self {
+        SimError::Io(e)
+    }
+}
+
+/// A [SimError] raised during a run, along with where in the run it happened.
+#[derive(Debug)]
+pub struct SimulationError {
+    pub error: SimError,
+    pub event_uid: usize,
+    pub event_type: String,
+    pub event: String,
+    pub time: usize,
+    pub event_count: usize,
+}
+
+impl SimulationError {
+    pub fn new<P>(
+        error: SimError,
+        event: &dyn Event<P>,
+        time: usize,
+        event_count: usize,
+    ) -> SimulationError {
+        SimulationError {
+            error,
+            event_uid: event.get_uid(),
+            event_type: event.get_event_type().to_string(),
+            event: event.to_string(),
+            time,
+            event_count,
+        }
+    }
+}
+
+impl Display for SimulationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(
+            f,
+            "{} (while applying {} at time {}, after {} events)",
+            self.error, self.event, self.time, self.event_count
+        )
+    }
+}
+
+impl std::error::Error for SimulationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
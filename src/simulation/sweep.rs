@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::simulation::replications::{
+    setup_error, ReplicationReport, ReplicationRuns, Replications,
+};
+use crate::simulation::sim::Simulation;
+use crate::simulation::sim_error::{SimError, SimulationError};
+use crate::statistics::stats::Stats;
+
+/// Named parameter values for one point of a sweep, e.g. `buses=5, pickup_delay=2`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SweepPoint(pub BTreeMap<String, usize>);
+
+impl SweepPoint {
+    /// The value of a parameter, an error if the point has no such parameter.
+    pub fn get(&self, name: &str) -> Result<usize, SimError> {
+        self.0.get(name).copied().ok_or_else(|| {
+            SimError::InvalidScenario(format!("sweep point {} has no parameter {}", self, name))
+        })
+    }
+}
+
+impl Display for SweepPoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let parameters: Vec<String> = self
+            .0
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        write!(f, "{}", parameters.join(", "))
+    }
+}
+
+/// Builds every combination of a set of parameter values.
+#[derive(Clone, Debug, Default)]
+pub struct Grid {
+    axes: Vec<(String, Vec<usize>)>,
+}
+
+impl Grid {
+    pub fn new() -> Grid {
+        Grid { axes: Vec::new() }
+    }
+
+    pub fn axis(mut self, name: &str, values: &[usize]) -> Grid {
+        self.axes.push((name.to_string(), values.to_vec()));
+        self
+    }
+
+    pub fn points(&self) -> Vec<SweepPoint> {
+        let mut points = vec![SweepPoint::default()];
+        for (name, values) in &self.axes {
+            points = points
+                .iter()
+                .flat_map(|point| {
+                    values.iter().map(move |value| {
+                        let mut point = point.clone();
+                        point.0.insert(name.clone(), *value);
+                        point
+                    })
+                })
+                .collect();
+        }
+        points
+    }
+}
+
+/// Runs replications of every point of a sweep on a pool of worker threads.
+pub struct Sweep<T> {
+    pub points: Vec<T>,
+    pub replications: Replications,
+    pub threads: usize,
+}
+
+/// The replications of one sweep point.
+pub struct SweepResult<T> {
+    pub point: T,
+    pub runs: ReplicationRuns,
+}
+
+impl<T: Sync> Sweep<T> {
+    /// Use one worker thread per available CPU.
+    pub fn new(points: Vec<T>, replications: Replications) -> Sweep<T> {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Sweep {
+            points,
+            replications,
+            threads,
+        }
+    }
+
+    /// Build and run a simulation for every point and replication seed.
+    /// Results come back in the order of the points, whatever order the
+    /// workers finished in. Once a job fails the workers stop taking new
+    /// jobs, and the error of the job that failed first is returned.
+    pub fn run<P, F>(self, factory: F) -> Result<Vec<SweepResult<T>>, SimulationError>
+    where
+        F: Fn(&T, u64) -> Result<Simulation<P>, SimError> + Sync,
+    {
+        self.replications
+            .validate()
+            .map_err(|error| setup_error(error, "checking replications"))?;
+        let seeds = self.replications.seeds();
+        let jobs: Vec<(usize, u64)> = (0..self.points.len())
+            .flat_map(|point| seeds.iter().map(move |seed| (point, *seed)))
+            .collect();
+        let next_job = AtomicUsize::new(0);
+        let abort = AtomicBool::new(false);
+        let failure: Mutex<Option<SimulationError>> = Mutex::new(None);
+        let finished: Mutex<Vec<Option<Stats>>> = Mutex::new(jobs.iter().map(|_| None).collect());
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.max(1) {
+                scope.spawn(|| loop {
+                    if abort.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let job = next_job.fetch_add(1, Ordering::Relaxed);
+                    let Some((point, seed)) = jobs.get(job) else {
+                        break;
+                    };
+                    match self
+                        .replications
+                        .run_one(*seed, |seed| factory(&self.points[*point], seed))
+                    {
+                        Ok(statistics) => finished.lock().unwrap()[job] = Some(statistics),
+                        Err(error) => {
+                            abort.store(true, Ordering::Relaxed);
+                            failure.lock().unwrap().get_or_insert(error);
+                        }
+                    }
+                });
+            }
+        });
+
+        if let Some(error) = failure.into_inner().unwrap() {
+            return Err(error);
+        }
+        let mut finished = finished.into_inner().unwrap().into_iter();
+        let mut results = Vec::new();
+        for point in self.points {
+            let mut statistics = Vec::new();
+            for result in finished.by_ref().take(seeds.len()) {
+                statistics.push(result.expect("every sweep job is run"));
+            }
+            results.push(SweepResult {
+                point,
+                runs: ReplicationRuns {
+                    seeds: seeds.clone(),
+                    statistics,
+                    confidence: self.replications.confidence,
+                },
+            });
+        }
+        Ok(results)
+    }
+}
+
+/// Tabulate one statistic across the sweep: one row per point, with the
+/// mean and confidence interval over its replications.
+pub fn sweep_table<T: Display>(results: &[SweepResult<T>], label: &str) -> String {
+    let reports: Vec<(String, Option<ReplicationReport>)> = results
+        .iter()
+        .map(|result| (result.point.to_string(), result.runs.report(label)))
+        .collect();
+    let width = reports
+        .iter()
+        .map(|(point, _)| point.chars().count())
+        .chain(std::iter::once("Parameters".len()))
+        .max()
+        .unwrap_or_default();
+
+    let mut output = String::new();
+    let underline = "=".repeat(label.chars().count());
+    output.push_str(&format!("{}\n{}\n{}\n", underline, label, underline));
+    output.push_str(&format!("{:<width$} | Mean | CI\n", "Parameters"));
+    for (point, report) in reports {
+        match report {
+            Some(report) => {
+                let interval = match report.interval() {
+                    Some((lower, upper)) => format!("{} to {}", lower, upper),
+                    None => String::from("-"),
+                };
+                output.push_str(&format!(
+                    "{:<width$} | {} | {}\n",
+                    point, report.mean, interval
+                ));
+            }
+            None => output.push_str(&format!("{:<width$} | - | -\n", point)),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{sweep_table, Grid, Sweep, SweepPoint};
+    use crate::environment::bus_world::bus_environment::BusEnvironment;
+    use crate::environment::bus_world::bus_world_events::bus_event::BusEvent;
+    use crate::simulation::replications::Replications;
+    use crate::simulation::sim::Simulation;
+    use crate::simulation::sim_error::SimError;
+    use crate::simulation::test_world::TestWorld;
+
+    fn scenario(point: &SweepPoint, seed: u64) -> Result<Simulation<BusEvent>, SimError> {
+        TestWorld {
+            seed,
+            pickup_delay: point.get("pickup_delay")?,
+            buses: point.get("buses")?,
+            ..TestWorld::default()
+        }
+        .build()
+    }
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn simulations_can_move_between_threads() {
+        assert_send::<Simulation<BusEvent>>();
+        assert_send::<BusEnvironment>();
+    }
+
+    #[test]
+    fn grid_builds_every_combination() {
+        let points = Grid::new()
+            .axis("buses", &[1, 2, 3])
+            .axis("pickup_delay", &[1, 2])
+            .points();
+        assert_eq!(points.len(), 6);
+        assert_eq!(points[1].to_string(), "buses=1, pickup_delay=2");
+        assert_eq!(points[5].get("buses").unwrap(), 3);
+        assert!(matches!(
+            points[5].get("capacity"),
+            Err(SimError::InvalidScenario(_))
+        ));
+    }
+
+    #[test]
+    fn parallel_sweep_matches_serial_replications() {
+        let points = Grid::new()
+            .axis("buses", &[1, 3])
+            .axis("pickup_delay", &[1, 4])
+            .points();
+        let mut sweep = Sweep::new(points.clone(), Replications::new(3, 20));
+        sweep.threads = 4;
+        let results = sweep.run(scenario).unwrap();
+
+        assert_eq!(results.len(), points.len());
+        for (result, point) in results.iter().zip(points.iter()) {
+            assert_eq!(&result.point, point);
+            let serial = Replications::new(3, 20)
+                .run(|seed| scenario(point, seed))
+                .unwrap();
+            assert_eq!(serial.seeds, result.runs.seeds);
+            assert_eq!(serial.statistics, result.runs.statistics);
+        }
+
+        let table = sweep_table(&results, "Passenger Wait Time");
+        assert_eq!(table.lines().count(), 4 + points.len());
+        assert!(table.contains("buses=3, pickup_delay=4"));
+    }
+
+    #[test]
+    fn a_failure_stops_the_sweep() {
+        let points = Grid::new().axis("buses", &[1, 2, 3]).points();
+        let mut sweep = Sweep::new(points, Replications::new(3, 20));
+        sweep.threads = 1;
+        let built = AtomicUsize::new(0);
+        let result = sweep.run(|point: &SweepPoint, seed| {
+            built.fetch_add(1, Ordering::Relaxed);
+            // every point lacks a pickup delay, so the first job fails
+            scenario(point, seed)
+        });
+        let error = result.err().unwrap();
+        assert!(matches!(error.error, SimError::InvalidScenario(_)));
+        assert_eq!(built.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,39 @@
+use crate::environment::bus_world::bus_environment::{BusEnvironment, BusEnvironmentSettings};
+use crate::environment::bus_world::bus_world_events::bus_event::BusEvent;
+use crate::environment::bus_world::bus_world_events::new_bus::{NewBusEvent, NewBusesJson};
+use crate::simulation::sim::Simulation;
+use crate::simulation::sim_error::SimError;
+
+/// The small bus world the simulation tests run: `stops` stops with
+/// `passengers` waiting between them, and `buses` buses of capacity 5
+/// created at time 0, running for 100 units of time.
+pub struct TestWorld {
+    pub seed: u64,
+    pub pickup_delay: usize,
+    pub stops: usize,
+    pub passengers: usize,
+    pub buses: usize,
+}
+
+impl Default for TestWorld {
+    fn default() -> Self {
+        TestWorld {
+            seed: 0,
+            pickup_delay: 1,
+            stops: 4,
+            passengers: 30,
+            buses: 2,
+        }
+    }
+}
+
+impl TestWorld {
+    pub fn build(&self) -> Result<Simulation<BusEvent>, SimError> {
+        let settings = BusEnvironmentSettings::new(self.pickup_delay, 2, 5, 10, self.seed);
+        let mut env = BusEnvironment::new(settings);
+        env.create_bus_stops(self.stops);
+        env.initialize_bus_stops_with_passengers(self.passengers)?;
+        let initial_event = Box::new(NewBusEvent::new(1, 0, NewBusesJson::new(self.buses, 5)));
+        Ok(Simulation::new(100, Box::new(env), initial_event))
+    }
+}
@@ -0,0 +1,336 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::environment::environment::Environment;
+use crate::event::event::{Event, EventRecord};
+#[cfg(feature = "json")]
+use {
+    crate::des::des::Scheduler,
+    crate::event::event::EventPayload,
+    crate::simulation::sim_error::{SimError, SimulationError},
+    crate::statistics::stats::Stats,
+    serde::de::DeserializeOwned,
+    std::fs::File,
+    std::io::{BufRead, BufWriter, Write},
+    std::path::Path,
+};
+
+/// One line of a trace. A trace starts with the environment's initial
+/// state and then lists every applied event in order.
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "kind")]
+pub enum TraceRecord<P> {
+    Start {
+        time: usize,
+        /// The handle the next scheduled event gets, so that handles the
+        /// environment keeps come out the same when replaying.
+        #[serde(default)]
+        next_sequence: u64,
+        environment_state: String,
+    },
+    Event {
+        time: usize,
+        event: EventRecord<P>,
+        state_digest: Option<String>,
+    },
+}
+
+/// Receives every event applied by a [Simulation].
+///
+/// [Simulation]: crate::simulation::sim::Simulation
+pub trait TraceSink<P>: Send {
+    /// Called once when the sink is attached, before any event is recorded.
+    fn start(
+        &mut self,
+        time: usize,
+        next_sequence: u64,
+        environment: &dyn Environment<Payload = P>,
+    ) -> io::Result<()>;
+
+    /// Called after each event has been applied to the environment.
+    fn record(
+        &mut self,
+        time: usize,
+        event: &dyn Event<P>,
+        environment: &dyn Environment<Payload = P>,
+    ) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "json")]
+/// Writes a trace as JSON Lines, one [TraceRecord] per line.
+pub struct JsonLinesTrace<W: Write> {
+    writer: W,
+    with_state_digest: bool,
+}
+
+#[cfg(feature = "json")]
+impl<W: Write> JsonLinesTrace<W> {
+    /// Computing the state digest serializes the whole environment after
+    /// every event, so it is optional.
+    pub fn new(writer: W, with_state_digest: bool) -> JsonLinesTrace<W> {
+        JsonLinesTrace {
+            writer,
+            with_state_digest,
+        }
+    }
+
+    fn write_record<P: Serialize>(&mut self, record: &TraceRecord<P>) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        writeln!(self.writer)
+    }
+}
+
+#[cfg(feature = "json")]
+impl JsonLinesTrace<BufWriter<File>> {
+    pub fn create<T: AsRef<Path>>(path: T, with_state_digest: bool) -> io::Result<Self> {
+        Ok(JsonLinesTrace::new(
+            BufWriter::new(File::create(path)?),
+            with_state_digest,
+        ))
+    }
+}
+
+#[cfg(feature = "json")]
+impl<P: Serialize + Clone, W: Write + Send> TraceSink<P> for JsonLinesTrace<W> {
+    fn start(
+        &mut self,
+        time: usize,
+        next_sequence: u64,
+        environment: &dyn Environment<Payload = P>,
+    ) -> io::Result<()> {
+        self.write_record::<P>(&TraceRecord::Start {
+            time,
+            next_sequence,
+            environment_state: environment.get_state(),
+        })
+    }
+
+    fn record(
+        &mut self,
+        time: usize,
+        event: &dyn Event<P>,
+        environment: &dyn Environment<Payload = P>,
+    ) -> io::Result<()> {
+        let state_digest = if self.with_state_digest {
+            Some(state_digest(&environment.get_state()))
+        } else {
+            None
+        };
+        self.write_record(&TraceRecord::Event {
+            time,
+            event: EventRecord::from_event(event),
+            state_digest,
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// A short, stable digest of an environment state (64 bit FNV-1a).
+/// Unlike std's hasher it is guaranteed not to change between builds,
+/// so traces stay comparable across versions.
+pub fn state_digest(state: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in state.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(feature = "json")]
+/// The result of replaying a trace.
+#[derive(Debug)]
+pub struct Replay {
+    pub events_replayed: usize,
+    pub digests_checked: usize,
+    pub statistics: Stats,
+}
+
+#[cfg(feature = "json")]
+impl Replay {
+    /// Whether any replayed state was compared with the trace. A trace
+    /// recorded without state digests replays without any check.
+    pub fn verified(&self) -> bool {
+        self.digests_checked > 0
+    }
+}
+
+#[cfg(feature = "json")]
+/// Feed a trace back through an environment, verifying after each event
+/// that the environment reaches the recorded state. The environment is
+/// reset to the trace's initial state first, so any instance of the right
+/// type can be passed in. Events the environment schedules while replaying
+/// are dropped: the trace is the source of truth for what happens next.
+/// Only traces with state digests are checked, see [Replay::verified].
+pub fn replay<P, R>(
+    environment: &mut dyn Environment<Payload = P>,
+    reader: R,
+) -> Result<Replay, SimulationError>
+where
+    P: EventPayload + DeserializeOwned,
+    R: BufRead,
+{
+    let mut scheduler = Scheduler::new(usize::MAX);
+    let mut replay = Replay {
+        events_replayed: 0,
+        digests_checked: 0,
+        statistics: Stats::new(),
+    };
+
+    for (index, line) in reader.lines().enumerate() {
+        let failure = |error: SimError, replay: &Replay, time: usize| SimulationError {
+            error,
+            event_uid: 0,
+            event_type: String::new(),
+            event: format!("trace line {}", index + 1),
+            time,
+            event_count: replay.events_replayed,
+        };
+        let current_time = scheduler.current_time;
+        let line = line.map_err(|e| failure(e.into(), &replay, current_time))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str::<TraceRecord<P>>(&line)
+            .map_err(|e| failure(e.into(), &replay, current_time))?;
+
+        match record {
+            TraceRecord::Start {
+                time,
+                next_sequence,
+                environment_state,
+            } => {
+                scheduler = Scheduler::restore(usize::MAX, time, next_sequence, Vec::new());
+                environment
+                    .restore_state(&environment_state)
+                    .map_err(|e| failure(e, &replay, time))?;
+            }
+            TraceRecord::Event {
+                time,
+                event,
+                state_digest: expected,
+            } => {
+                scheduler.current_time = time;
+                let event = event.into_event();
+                let event_failure = |error: SimError, replay: &Replay| {
+                    SimulationError::new(error, event.as_ref(), time, replay.events_replayed)
+                };
+                environment
+                    .apply_event(&mut scheduler, &mut replay.statistics, event.as_ref())
+                    .map_err(|e| event_failure(e, &replay))?;
+                // drop what the event scheduled, peeking discards the stale queue entries
+                for handle in scheduler.take_recently_scheduled() {
+                    scheduler.cancel(handle);
+                }
+                scheduler.peek_time();
+                if let Some(expected) = expected {
+                    let found = state_digest(&environment.get_state());
+                    if found != expected {
+                        return Err(event_failure(
+                            SimError::InvalidState(format!(
+                                "state digest {} does not match the traced digest {}",
+                                found, expected
+                            )),
+                            &replay,
+                        ));
+                    }
+                    replay.digests_checked += 1;
+                }
+                replay.events_replayed += 1;
+            }
+        }
+    }
+    Ok(replay)
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use std::fs;
+    use std::io::BufReader;
+
+    use super::{replay, JsonLinesTrace};
+    use crate::environment::bus_world::bus_environment::BusEnvironment;
+    use crate::environment::bus_world::bus_world_events::bus_event::BusEvent;
+    use crate::environment::environment::Environment;
+    use crate::simulation::sim::Simulation;
+    use crate::simulation::sim_error::SimError;
+    use crate::simulation::test_world::TestWorld;
+
+    fn traced_run(file_name: &str) -> (Simulation<BusEvent>, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(file_name);
+        let mut sim = TestWorld {
+            seed: 5,
+            passengers: 40,
+            ..TestWorld::default()
+        }
+        .build()
+        .unwrap();
+        sim.record_trace(Box::new(JsonLinesTrace::create(&path, true).unwrap()))
+            .unwrap();
+        sim.run().unwrap();
+        (sim, path)
+    }
+
+    #[test]
+    fn replay_reaches_traced_state() {
+        let (sim, path) = traced_run("des_replay_reaches_traced_state.jsonl");
+        let mut env = BusEnvironment::default();
+        let file = BufReader::new(fs::File::open(&path).unwrap());
+        let replayed = replay(&mut env, file).unwrap();
+
+        // every event plus the terminating event
+        assert_eq!(replayed.events_replayed, sim.event_count() + 1);
+        assert_eq!(replayed.digests_checked, replayed.events_replayed);
+        assert!(replayed.verified());
+        assert_eq!(env.get_state(), sim.environment.get_state());
+        assert_eq!(
+            replayed
+                .statistics
+                .get_series_by_name("Passenger Wait Time".to_string()),
+            sim.statistics
+                .get_series_by_name("Passenger Wait Time".to_string())
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn traces_without_digests_are_not_verified() {
+        let path = std::env::temp_dir().join("des_traces_without_digests.jsonl");
+        let mut sim = TestWorld::default().build().unwrap();
+        sim.record_trace(Box::new(JsonLinesTrace::create(&path, false).unwrap()))
+            .unwrap();
+        sim.run().unwrap();
+
+        let mut env = BusEnvironment::default();
+        let file = BufReader::new(fs::File::open(&path).unwrap());
+        let replayed = replay(&mut env, file).unwrap();
+        assert_eq!(replayed.events_replayed, sim.event_count() + 1);
+        assert!(!replayed.verified());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn replay_detects_diverging_state() {
+        let (_, path) = traced_run("des_replay_detects_diverging_state.jsonl");
+        let trace = fs::read_to_string(&path).unwrap();
+        // corrupt the digest of the third event
+        let mut lines: Vec<String> = trace.lines().map(|l| l.to_string()).collect();
+        let mut record: serde_json::Value = serde_json::from_str(&lines[3]).unwrap();
+        record["state_digest"] = serde_json::Value::String("0000000000000000".to_string());
+        lines[3] = record.to_string();
+
+        let mut env = BusEnvironment::default();
+        let failure = replay(&mut env, lines.join("\n").as_bytes()).unwrap_err();
+        assert!(matches!(failure.error, SimError::InvalidState(_)));
+        assert_eq!(failure.event_count, 2);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+use std::error::Error;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crossterm::event::{self as terminal_event, KeyCode, KeyEventKind};
+use crossterm::{cursor, execute, queue, style, terminal};
+
+use crate::simulation::sim::Simulation;
+use crate::statistics::timeseries::{SeriesKind, TimeSeries};
+
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const UPCOMING_EVENTS: usize = 8;
+const MIN_DELAY_MILLIS: u64 = 1;
+const MAX_DELAY_MILLIS: u64 = 5000;
+
+/// What the user asked for with the last key press.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TuiAction {
+    None,
+    Step,
+    JumpTo(usize),
+    Quit,
+}
+
+/// Everything the interactive display remembers between frames.
+#[derive(Debug)]
+pub struct TuiState {
+    pub paused: bool,
+    pub delay_millis: u64,
+    /// Labels of the statistics drawn as sparklines.
+    pub charts: Vec<String>,
+    /// Digits typed after `g`, while waiting for Enter.
+    pub jump_input: Option<String>,
+    pub message: String,
+}
+
+impl TuiState {
+    pub fn new(delay_millis: u64, charts: Vec<String>) -> TuiState {
+        TuiState {
+            paused: true,
+            delay_millis: delay_millis.clamp(MIN_DELAY_MILLIS, MAX_DELAY_MILLIS),
+            charts,
+            jump_input: None,
+            message: String::from("paused, press space to start"),
+        }
+    }
+
+    /// Update the state for a key press and say what the simulation should do.
+    pub fn handle_key(&mut self, key: KeyCode) -> TuiAction {
+        if let Some(input) = self.jump_input.as_mut() {
+            match key {
+                KeyCode::Char(digit) if digit.is_ascii_digit() => input.push(digit),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    let input = self.jump_input.take().unwrap_or_default();
+                    return match input.parse() {
+                        Ok(time) => TuiAction::JumpTo(time),
+                        Err(_) => {
+                            self.message = format!("not a time: {:?}", input);
+                            TuiAction::None
+                        }
+                    };
+                }
+                KeyCode::Esc => self.jump_input = None,
+                _ => {}
+            }
+            return TuiAction::None;
+        }
+
+        match key {
+            KeyCode::Char(' ') | KeyCode::Char('p') => {
+                self.paused = !self.paused;
+                self.message = String::from(if self.paused { "paused" } else { "running" });
+            }
+            KeyCode::Char('s') | KeyCode::Right => {
+                self.paused = true;
+                return TuiAction::Step;
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Up => {
+                self.delay_millis = (self.delay_millis / 2).max(MIN_DELAY_MILLIS);
+            }
+            KeyCode::Char('-') | KeyCode::Down => {
+                self.delay_millis = (self.delay_millis * 2).min(MAX_DELAY_MILLIS);
+            }
+            KeyCode::Char('g') => self.jump_input = Some(String::new()),
+            KeyCode::Char('q') | KeyCode::Esc => return TuiAction::Quit,
+            _ => {}
+        }
+        TuiAction::None
+    }
+}
+
+/// Draw `values` as a line of block characters scaled between their
+/// minimum and maximum, keeping only the most recent `width` values.
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    let values = &values[values.len().saturating_sub(width)..];
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|value| {
+            if max > min {
+                let level = (value - min) / (max - min) * (SPARK_LEVELS.len() - 1) as f64;
+                SPARK_LEVELS[level.round() as usize]
+            } else {
+                SPARK_LEVELS[0]
+            }
+        })
+        .collect()
+}
+
+/// The statistics charted when none are chosen: every level series,
+/// e.g. the buses at each stop.
+pub fn default_charts(series: &[TimeSeries]) -> Vec<String> {
+    let mut labels: Vec<String> = series
+        .iter()
+        .filter(|series| series.kind == SeriesKind::Level)
+        .map(|series| series.statistic_label.clone())
+        .collect();
+    labels.sort();
+    labels
+}
+
+/// Lay out one frame: status, controls, the environment, upcoming
+/// events and a sparkline per charted statistic.
+pub fn render_frame<P>(sim: &Simulation<P>, state: &TuiState, width: usize) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "time {} / {}   events {}   delay {} ms   {}",
+            sim.current_time(),
+            sim.runtime(),
+            sim.event_count(),
+            state.delay_millis,
+            if sim.is_finished() {
+                "finished"
+            } else if state.paused {
+                "paused"
+            } else {
+                "running"
+            }
+        ),
+        String::from("[space] pause/resume  [s] step  [+/-] speed  [g] jump to time  [q] quit"),
+        match &state.jump_input {
+            Some(input) => format!("jump to time: {}_", input),
+            None => state.message.clone(),
+        },
+        String::new(),
+    ];
+
+    lines.push(String::from("Environment"));
+    lines.extend(sim.environment.status_lines());
+    lines.push(String::new());
+
+    let upcoming = sim.upcoming_events();
+    lines.push(format!("Upcoming events ({})", upcoming.len()));
+    for (_, event) in upcoming.iter().take(UPCOMING_EVENTS) {
+        lines.push(format!(
+            "{:>6}  {:<16} {}",
+            event.get_time_stamp(),
+            event.get_event_type(),
+            event
+        ));
+    }
+    lines.push(String::new());
+
+    let label_width = state
+        .charts
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or_default();
+    let chart_width = width.saturating_sub(label_width + 12).max(1);
+    for label in &state.charts {
+        let values: Vec<f64> = sim
+            .statistics
+            .get_series_by_name(label.clone())
+            .map(|series| series.observations().map(|(_, value)| value).collect())
+            .unwrap_or_default();
+        lines.push(format!(
+            "{:<label_width$} {} {}",
+            label,
+            sparkline(&values, chart_width),
+            values.last().map_or(String::from("-"), |v| v.to_string())
+        ));
+    }
+
+    lines
+        .into_iter()
+        .map(|line| line.chars().take(width).collect())
+        .collect()
+}
+
+/// Puts the terminal back the way it was, even if the display fails.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn draw<P>(sim: &Simulation<P>, state: &TuiState) -> io::Result<()> {
+    let (width, height) = terminal::size()?;
+    let mut stdout = io::stdout();
+    queue!(
+        stdout,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, 0)
+    )?;
+    for (row, line) in render_frame(sim, state, width as usize)
+        .iter()
+        .take(height as usize)
+        .enumerate()
+    {
+        queue!(stdout, cursor::MoveTo(0, row as u16), style::Print(line))?;
+    }
+    stdout.flush()
+}
+
+/// Run a simulation in an interactive terminal display until the user quits.
+/// The simulation starts paused. Charts show the statistics in `charts`,
+/// or every level series if it is empty.
+pub fn run_tui<P>(
+    sim: &mut Simulation<P>,
+    delay_millis: u64,
+    charts: Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    let mut state = TuiState::new(delay_millis, charts);
+    let _guard = TerminalGuard::enter()?;
+
+    loop {
+        if state.charts.is_empty() {
+            state.charts = default_charts(&sim.statistics.all_series);
+        }
+        draw(sim, &state)?;
+
+        let running = !state.paused && !sim.is_finished();
+        let key = if !running || terminal_event::poll(Duration::from_millis(state.delay_millis))? {
+            match terminal_event::read()? {
+                terminal_event::Event::Key(key) if key.kind != KeyEventKind::Release => {
+                    Some(key.code)
+                }
+                _ => continue,
+            }
+        } else {
+            None
+        };
+
+        let action = match key {
+            Some(code) => state.handle_key(code),
+            // nothing pressed before the delay ran out
+            None => TuiAction::Step,
+        };
+        match action {
+            TuiAction::None => {}
+            TuiAction::Quit => break,
+            TuiAction::Step => {
+                if sim.step()?.is_none() && !sim.is_finished() {
+                    sim.finish()?;
+                    state.paused = true;
+                    state.message = String::from("finished, press q to quit");
+                }
+            }
+            TuiAction::JumpTo(time) => {
+                let applied = sim.run_until(time)?;
+                state.message = format!("applied {} events", applied);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyCode;
+
+    use super::{render_frame, sparkline, TuiAction, TuiState};
+    use crate::environment::bus_world::bus_scenario::BusScenario;
+
+    #[test]
+    fn keys_control_the_run() {
+        let mut state = TuiState::new(100, Vec::new());
+        assert!(state.paused);
+        assert_eq!(state.handle_key(KeyCode::Char(' ')), TuiAction::None);
+        assert!(!state.paused);
+        state.handle_key(KeyCode::Char('+'));
+        assert_eq!(state.delay_millis, 50);
+        state.handle_key(KeyCode::Char('-'));
+        state.handle_key(KeyCode::Char('-'));
+        assert_eq!(state.delay_millis, 200);
+
+        assert_eq!(state.handle_key(KeyCode::Char('s')), TuiAction::Step);
+        assert!(state.paused);
+
+        state.handle_key(KeyCode::Char('g'));
+        for key in ['4', '2', 'x', '5'] {
+            state.handle_key(KeyCode::Char(key));
+        }
+        state.handle_key(KeyCode::Backspace);
+        assert_eq!(state.handle_key(KeyCode::Enter), TuiAction::JumpTo(42));
+        assert_eq!(state.handle_key(KeyCode::Char('q')), TuiAction::Quit);
+    }
+
+    #[test]
+    fn sparkline_scales_to_range() {
+        assert_eq!(sparkline(&[0.0, 7.0, 3.5, 7.0], 10), "▁█▅█");
+        assert_eq!(sparkline(&[1.0, 2.0, 3.0], 2), "▁█");
+        assert_eq!(sparkline(&[4.0, 4.0], 5), "▁▁");
+        assert_eq!(sparkline(&[], 5), "");
+    }
+
+    #[test]
+    fn frame_shows_stops_events_and_charts() {
+        let scenario = BusScenario::load("./test_data/scenarios/two_routes.toml").unwrap();
+        let mut sim = scenario.build().unwrap();
+        sim.run_until(40).unwrap();
+        let state = TuiState::new(100, vec!["stop B: buses".to_string()]);
+        let frame = render_frame(&sim, &state, 120);
+
+        assert!(frame[0].starts_with("time 40 / 200"));
+        assert!(frame
+            .iter()
+            .any(|line| line.starts_with("A ") && line.contains("waiting")));
+        let upcoming = frame
+            .iter()
+            .position(|line| line.starts_with("Upcoming events"))
+            .unwrap();
+        assert!(frame[upcoming + 1]
+            .trim_start()
+            .starts_with(|c: char| c.is_ascii_digit()));
+        assert!(frame.last().unwrap().starts_with("stop B: buses"));
+        assert!(frame.iter().all(|line| line.chars().count() <= 120));
+    }
+}
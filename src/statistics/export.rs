@@ -0,0 +1,185 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::stats::Stats;
+use super::timeseries::SeriesKind;
+
+/// One observation of one series. Every export format writes these fields,
+/// in this order, so results can be loaded as a flat table.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct StatRecord {
+    pub label: String,
+    pub unit: String,
+    pub kind: SeriesKind,
+    pub timestamp: usize,
+    pub value: f64,
+    /// Whether the observation was made during the warm-up period.
+    pub warm_up: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    /// A single JSON array of records.
+    Json,
+    /// One JSON record per line.
+    JsonLines,
+}
+
+impl ExportFormat {
+    /// Pick the format from a file extension: `csv`, `json`, `jsonl` or `ndjson`.
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Option<ExportFormat> {
+        match path.as_ref().extension()?.to_str()? {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            "jsonl" | "ndjson" => Some(ExportFormat::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+impl Stats {
+    /// Every observation of every series, series by series in the order
+    /// they were first recorded.
+    pub fn records(&self) -> Vec<StatRecord> {
+        self.all_series
+            .iter()
+            .flat_map(|series| {
+                series.observations().map(|(timestamp, value)| StatRecord {
+                    label: series.statistic_label.clone(),
+                    unit: series.unit.clone(),
+                    kind: series.kind,
+                    timestamp,
+                    value,
+                    warm_up: timestamp < series.warm_up_end,
+                })
+            })
+            .collect()
+    }
+
+    /// Write a header row and then one row per observation.
+    pub fn write_csv<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        for record in self.records() {
+            writer.serialize(record)?;
+        }
+        writer.flush()
+    }
+
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        serde_json::to_writer(&mut writer, &self.records())?;
+        writer.flush()
+    }
+
+    pub fn write_json_lines<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for record in self.records() {
+            serde_json::to_writer(&mut writer, &record)?;
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+
+    pub fn export<W: Write>(&self, writer: W, format: ExportFormat) -> io::Result<()> {
+        match format {
+            ExportFormat::Csv => self.write_csv(writer),
+            ExportFormat::Json => self.write_json(writer),
+            ExportFormat::JsonLines => self.write_json_lines(writer),
+        }
+    }
+
+    /// Export to a file, choosing the format from its extension.
+    pub fn export_to_file<T: AsRef<Path>>(&self, path: T) -> io::Result<()> {
+        let format = ExportFormat::from_path(&path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "cannot tell the export format of {}",
+                    path.as_ref().display()
+                ),
+            )
+        })?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.export(&mut writer, format)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExportFormat, StatRecord};
+    use crate::statistics::data_point::DataPoint;
+    use crate::statistics::stats::Stats;
+    use crate::statistics::timeseries::SeriesKind;
+
+    fn example_stats() -> Stats {
+        let mut stats = Stats::new();
+        stats.set_warm_up_end(5);
+        stats.add_statistic(
+            DataPoint::new(0, 3.0, "ms".to_string()),
+            "wait, total".to_string(),
+        );
+        stats.add_statistic(
+            DataPoint::new(10, 4.5, "ms".to_string()),
+            "wait, total".to_string(),
+        );
+        stats.add_level(
+            DataPoint::new(10, 2.0, "bus_count".to_string()),
+            "stop A: buses".to_string(),
+        );
+        stats
+    }
+
+    #[test]
+    fn csv_has_one_row_per_observation() {
+        let mut output = Vec::new();
+        example_stats().write_csv(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "label,unit,kind,timestamp,value,warm_up\n\
+             \"wait, total\",ms,Sampled,0,3.0,true\n\
+             \"wait, total\",ms,Sampled,10,4.5,false\n\
+             stop A: buses,bus_count,Level,10,2.0,false\n"
+        );
+    }
+
+    #[test]
+    fn json_formats_round_trip() {
+        let stats = example_stats();
+        let expected = stats.records();
+
+        let mut json = Vec::new();
+        stats.export(&mut json, ExportFormat::Json).unwrap();
+        let from_json: Vec<StatRecord> = serde_json::from_slice(&json).unwrap();
+        assert_eq!(from_json, expected);
+
+        let mut json_lines = Vec::new();
+        stats
+            .export(&mut json_lines, ExportFormat::JsonLines)
+            .unwrap();
+        let from_lines: Vec<StatRecord> = String::from_utf8(json_lines)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(from_lines, expected);
+        assert_eq!(expected[2].kind, SeriesKind::Level);
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(ExportFormat::from_path("out.csv"), Some(ExportFormat::Csv));
+        assert_eq!(
+            ExportFormat::from_path("out.json"),
+            Some(ExportFormat::Json)
+        );
+        assert_eq!(
+            ExportFormat::from_path("out.jsonl"),
+            Some(ExportFormat::JsonLines)
+        );
+        assert_eq!(ExportFormat::from_path("out.txt"), None);
+        assert!(example_stats().export_to_file("out.txt").is_err());
+    }
+}
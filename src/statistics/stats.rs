@@ -1,17 +1,52 @@
-use super::{data_point::DataPoint, timeseries::TimeSeries};
+use serde::{Deserialize, Serialize};
 
+use super::{
+    data_point::DataPoint,
+    timeseries::{SeriesKind, TimeSeries},
+};
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
 pub struct Stats {
     pub all_series: Vec<TimeSeries>,
+    /// End of the warm-up period, applied to every series including ones
+    /// created later.
+    #[serde(default)]
+    pub warm_up_end: usize,
 }
 
 impl Stats {
     pub fn new() -> Stats {
         Stats {
             all_series: Vec::new(),
+            warm_up_end: 0,
+        }
+    }
+
+    /// Tag everything recorded before `time` as warm-up data.
+    pub fn set_warm_up_end(&mut self, time: usize) {
+        self.warm_up_end = time;
+        for series in self.all_series.iter_mut() {
+            series.warm_up_end = time;
         }
     }
 
+    /// Throw away every recorded series, keeping the warm-up setting.
+    pub fn reset(&mut self) {
+        self.all_series.clear();
+    }
+
+    /// Record an observation, such as the number of passengers loaded by one event.
     pub fn add_statistic(&mut self, data_point: DataPoint, label: String) {
+        self.add_data_point(data_point, label, SeriesKind::Sampled);
+    }
+
+    /// Record a new value for a quantity that holds until its next change,
+    /// such as the number of buses at a stop.
+    pub fn add_level(&mut self, data_point: DataPoint, label: String) {
+        self.add_data_point(data_point, label, SeriesKind::Level);
+    }
+
+    fn add_data_point(&mut self, data_point: DataPoint, label: String, kind: SeriesKind) {
         let mut found = false;
         for series in self.all_series.iter_mut() {
             if series.statistic_label == label {
@@ -22,6 +57,8 @@ impl Stats {
         }
         if !found {
             let mut new_series = TimeSeries::new(label);
+            new_series.kind = kind;
+            new_series.warm_up_end = self.warm_up_end;
             new_series.add_data_point(&data_point);
             self.all_series.push(new_series);
         }
@@ -35,7 +72,7 @@ impl Stats {
 
 #[cfg(test)]
 mod tests {
-    use super::{DataPoint, Stats};
+    use super::{DataPoint, SeriesKind, Stats};
     use std::collections::BTreeMap;
 
     #[test]
@@ -52,6 +89,40 @@ mod tests {
         stats.add_statistic(data_point, "test".to_string());
         assert_eq!(stats.all_series.len(), 1);
         assert_eq!(stats.all_series[0].series.len(), 1);
-        assert_eq!(stats.all_series[0].series, BTreeMap::from([(0, 1.0)]));
+        assert_eq!(stats.all_series[0].series, BTreeMap::from([(0, vec![1.0])]));
+    }
+
+    #[test]
+    fn warm_up_applies_to_new_series() {
+        let mut stats = Stats::new();
+        stats.add_statistic(
+            DataPoint::new(0, 1.0, "unit".to_string()),
+            "old".to_string(),
+        );
+        stats.set_warm_up_end(10);
+        stats.add_statistic(
+            DataPoint::new(12, 1.0, "unit".to_string()),
+            "new".to_string(),
+        );
+        assert!(stats.all_series.iter().all(|s| s.warm_up_end == 10));
+
+        stats.reset();
+        assert!(stats.all_series.is_empty());
+        assert_eq!(stats.warm_up_end, 10);
+    }
+
+    #[test]
+    fn add_level_creates_level_series() {
+        let mut stats = Stats::new();
+        stats.add_level(
+            DataPoint::new(0, 2.0, "unit".to_string()),
+            "level".to_string(),
+        );
+        stats.add_statistic(
+            DataPoint::new(0, 2.0, "unit".to_string()),
+            "sample".to_string(),
+        );
+        assert_eq!(stats.all_series[0].kind, SeriesKind::Level);
+        assert_eq!(stats.all_series[1].kind, SeriesKind::Sampled);
     }
 }
@@ -1,14 +1,67 @@
 use super::data_point::DataPoint;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
     fmt::{self, Display, Formatter},
 };
 
-#[derive(Clone)]
+/// How the values of a series relate to simulated time.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SeriesKind {
+    /// Independent observations made at a point in time, e.g. the number of
+    /// passengers loaded by one event.
+    #[default]
+    Sampled,
+    /// A piecewise-constant quantity: each value holds until the next one,
+    /// e.g. the number of buses waiting at a stop.
+    Level,
+}
+
+/// How several observations made at the same timestamp are combined into one value.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregation {
+    Sum,
+    Last,
+    Max,
+    Mean,
+}
+
+impl Aggregation {
+    fn apply(&self, values: &[f64]) -> f64 {
+        match self {
+            Aggregation::Sum => values.iter().sum(),
+            Aggregation::Last => *values.last().unwrap_or(&0.0),
+            Aggregation::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregation::Mean => values.iter().sum::<f64>() / values.len() as f64,
+        }
+    }
+}
+
+/// Summary of the steady state data of a series.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub sum: f64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Sample standard deviation, 0 for a single observation.
+    pub std_dev: f64,
+    pub median: f64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 pub struct TimeSeries {
     pub statistic_label: String,
     pub unit: String,
-    pub series: BTreeMap<usize, f64>,
+    #[serde(default)]
+    pub kind: SeriesKind,
+    /// Every observation, in the order they were recorded at each timestamp.
+    pub series: BTreeMap<usize, Vec<f64>>,
+    /// Data points before this time were recorded while the simulation was
+    /// warming up, and are left out of summaries.
+    #[serde(default)]
+    pub warm_up_end: usize,
 }
 
 impl TimeSeries {
@@ -16,7 +69,9 @@ impl TimeSeries {
         TimeSeries {
             statistic_label,
             unit: String::from("Unknown"),
+            kind: SeriesKind::Sampled,
             series: BTreeMap::new(),
+            warm_up_end: 0,
         }
     }
 
@@ -24,10 +79,149 @@ impl TimeSeries {
         if self.unit == "Unknown" {
             self.unit = data_point.unit.clone();
         }
-        self.series.insert(data_point.timestamp, data_point.value);
+        self.series
+            .entry(data_point.timestamp)
+            .or_default()
+            .push(data_point.value);
+    }
+
+    /// Every observation as `(timestamp, value)`, in recording order.
+    pub fn observations(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        flatten(self.series.range(..))
+    }
+
+    /// The observations recorded during the warm-up period.
+    pub fn warm_up(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        flatten(self.series.range(..self.warm_up_end))
+    }
+
+    /// The observations recorded after the warm-up period.
+    pub fn steady_state(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        flatten(self.series.range(self.warm_up_end..))
+    }
+
+    fn steady_state_values(&self) -> Vec<f64> {
+        self.steady_state().map(|(_, value)| value).collect()
+    }
+
+    /// One value per timestamp, combining observations that share a timestamp.
+    pub fn aggregated(&self, policy: Aggregation) -> BTreeMap<usize, f64> {
+        self.series
+            .iter()
+            .map(|(timestamp, values)| (*timestamp, policy.apply(values)))
+            .collect()
+    }
+
+    /// The number of steady state observations.
+    pub fn count(&self) -> usize {
+        self.steady_state().count()
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.steady_state().map(|(_, value)| value).sum()
+    }
+
+    /// The mean of the steady state data, or `None` if there is none.
+    /// For a [SeriesKind::Level] series this weighs every change equally;
+    /// use [TimeSeries::time_weighted_mean] to weigh values by how long they held.
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        Some(self.sum() / count as f64)
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        self.steady_state().map(|(_, value)| value).reduce(f64::min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        self.steady_state().map(|(_, value)| value).reduce(f64::max)
+    }
+
+    /// The sample standard deviation of the steady state data.
+    pub fn std_dev(&self) -> Option<f64> {
+        let values = self.steady_state_values();
+        let mean = self.mean()?;
+        if values.len() < 2 {
+            return Some(0.0);
+        }
+        let squares: f64 = values.iter().map(|value| (value - mean).powi(2)).sum();
+        Some((squares / (values.len() - 1) as f64).sqrt())
+    }
+
+    /// The `p`th percentile (0 to 100) of the steady state data,
+    /// interpolating linearly between the closest observations.
+    pub fn percentile(&self, p: f64) -> Option<f64> {
+        let mut values = self.steady_state_values();
+        if values.is_empty() || !(0.0..=100.0).contains(&p) {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let rank = p / 100.0 * (values.len() - 1) as f64;
+        let lower = rank.floor() as usize;
+        let upper = rank.ceil() as usize;
+        Some(values[lower] + (values[upper] - values[lower]) * (rank - lower as f64))
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        Some(Summary {
+            count: self.count(),
+            sum: self.sum(),
+            mean: self.mean()?,
+            min: self.min()?,
+            max: self.max()?,
+            std_dev: self.std_dev()?,
+            median: self.percentile(50.0)?,
+        })
+    }
+
+    /// The area under a [SeriesKind::Level] series between `start` and
+    /// `end`. Of several changes at one timestamp the last one holds. The
+    /// level is unknown before the first data point, so the interval is cut
+    /// to start there. `None` for sampled series, which have
+    /// no value between observations, or if nothing is known about the interval.
+    pub fn integral(&self, start: usize, end: usize) -> Option<f64> {
+        let (start, end) = self.integration_interval(start, end)?;
+        let levels = self.aggregated(Aggregation::Last);
+        let mut level = *levels.range(..=start).next_back()?.1;
+        let mut since = start;
+        let mut area = 0.0;
+        for (timestamp, value) in levels.range(start + 1..end) {
+            area += level * (timestamp - since) as f64;
+            level = *value;
+            since = *timestamp;
+        }
+        Some(area + level * (end - since) as f64)
+    }
+
+    /// The mean value of a [SeriesKind::Level] series over simulated time
+    /// between `start` and `end`, see [TimeSeries::integral].
+    pub fn time_weighted_mean(&self, start: usize, end: usize) -> Option<f64> {
+        let (from, to) = self.integration_interval(start, end)?;
+        Some(self.integral(start, end)? / (to - from) as f64)
+    }
+
+    fn integration_interval(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        if self.kind != SeriesKind::Level {
+            return None;
+        }
+        let first = *self.series.keys().next()?;
+        let start = start.max(first);
+        if start >= end {
+            return None;
+        }
+        Some((start, end))
     }
 }
 
+fn flatten<'a>(
+    range: impl Iterator<Item = (&'a usize, &'a Vec<f64>)> + 'a,
+) -> impl Iterator<Item = (usize, f64)> + 'a {
+    range.flat_map(|(timestamp, values)| values.iter().map(move |value| (*timestamp, *value)))
+}
+
 impl Display for TimeSeries {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let mut output = String::new();
@@ -41,7 +235,7 @@ impl Display for TimeSeries {
         }
         output.push('\n');
         output.push_str(&format!("Timestamp | {}\n", self.unit));
-        for (timestamp, value) in &self.series {
+        for (timestamp, value) in self.observations() {
             output.push_str(&format!("{:<9} | {}\n", timestamp, value));
         }
         write!(f, "{}", output)
@@ -52,7 +246,23 @@ impl Display for TimeSeries {
 mod tests {
     use std::fs;
 
-    use crate::statistics::{data_point::DataPoint, timeseries::TimeSeries};
+    use crate::statistics::{
+        data_point::DataPoint,
+        timeseries::{Aggregation, SeriesKind, TimeSeries},
+    };
+
+    fn series_of(kind: SeriesKind, points: &[(usize, f64)]) -> TimeSeries {
+        let mut time_series = TimeSeries::new("test".to_string());
+        time_series.kind = kind;
+        for (timestamp, value) in points {
+            time_series.add_data_point(&DataPoint::new(
+                *timestamp,
+                *value,
+                "fake_unit".to_string(),
+            ));
+        }
+        time_series
+    }
 
     #[test]
     fn create_new_timeseries() {
@@ -67,7 +277,110 @@ mod tests {
         let data_point = DataPoint::new(0, 1.0, String::from("fake_unit"));
         time_series.add_data_point(&data_point);
         assert_eq!(time_series.series.len(), 1);
-        assert_eq!(time_series.series.get(&0), Some(&1.0));
+        assert_eq!(time_series.series.get(&0), Some(&vec![1.0]));
+    }
+
+    #[test]
+    fn shared_timestamps_keep_every_observation() {
+        let mut time_series = TimeSeries::new("test".to_string());
+        // many buses loading passengers in the same tick
+        for value in 1..=100 {
+            time_series.add_data_point(&DataPoint::new(7, value as f64, "fake_unit".to_string()));
+        }
+        time_series.add_data_point(&DataPoint::new(8, 1.0, "fake_unit".to_string()));
+
+        assert_eq!(time_series.count(), 101);
+        assert_eq!(time_series.sum(), 5051.0);
+        assert_eq!(
+            time_series.series[&7],
+            (1..=100).map(|v| v as f64).collect::<Vec<_>>()
+        );
+
+        assert_eq!(time_series.aggregated(Aggregation::Sum)[&7], 5050.0);
+        assert_eq!(time_series.aggregated(Aggregation::Last)[&7], 100.0);
+        assert_eq!(time_series.aggregated(Aggregation::Max)[&7], 100.0);
+        assert_eq!(time_series.aggregated(Aggregation::Mean)[&7], 50.5);
+        assert_eq!(time_series.aggregated(Aggregation::Sum)[&8], 1.0);
+    }
+
+    #[test]
+    fn level_series_uses_last_change_at_a_timestamp() {
+        // a bus leaves and two arrive at time 10
+        let time_series = series_of(
+            SeriesKind::Level,
+            &[(0, 1.0), (10, 0.0), (10, 1.0), (10, 2.0)],
+        );
+        assert_eq!(time_series.integral(0, 20), Some(30.0));
+    }
+
+    #[test]
+    fn summaries_exclude_warm_up() {
+        let mut time_series = TimeSeries::new("test".to_string());
+        for (timestamp, value) in [(0, 100.0), (5, 50.0), (10, 2.0), (15, 4.0)] {
+            time_series.add_data_point(&DataPoint::new(timestamp, value, "fake_unit".to_string()));
+        }
+        assert_eq!(time_series.mean(), Some(39.0));
+
+        time_series.warm_up_end = 10;
+        assert_eq!(time_series.warm_up().count(), 2);
+        assert_eq!(time_series.steady_state().count(), 2);
+        assert_eq!(time_series.mean(), Some(3.0));
+
+        time_series.warm_up_end = 20;
+        assert_eq!(time_series.mean(), None);
+    }
+
+    #[test]
+    fn summary_of_sampled_series() {
+        let time_series = series_of(
+            SeriesKind::Sampled,
+            &[
+                (0, 2.0),
+                (1, 4.0),
+                (2, 4.0),
+                (3, 4.0),
+                (4, 5.0),
+                (5, 5.0),
+                (6, 7.0),
+                (7, 9.0),
+            ],
+        );
+        let summary = time_series.summary().unwrap();
+        assert_eq!(summary.count, 8);
+        assert_eq!(summary.sum, 40.0);
+        assert_eq!(summary.mean, 5.0);
+        assert_eq!(summary.min, 2.0);
+        assert_eq!(summary.max, 9.0);
+        assert!((summary.std_dev - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+        assert_eq!(summary.median, 4.5);
+        assert_eq!(time_series.percentile(0.0), Some(2.0));
+        assert_eq!(time_series.percentile(100.0), Some(9.0));
+        assert_eq!(time_series.percentile(101.0), None);
+        // sampled observations have no value between them
+        assert_eq!(time_series.time_weighted_mean(0, 10), None);
+        assert_eq!(TimeSeries::new("empty".to_string()).summary(), None);
+    }
+
+    #[test]
+    fn level_series_integrates_over_time() {
+        // 1 bus from 0 to 10, 3 buses from 10 to 12, none from 12 on
+        let time_series = series_of(SeriesKind::Level, &[(0, 1.0), (10, 3.0), (12, 0.0)]);
+        assert_eq!(time_series.integral(0, 20), Some(16.0));
+        assert_eq!(time_series.time_weighted_mean(0, 20), Some(0.8));
+        // the plain mean weighs each change equally
+        assert_eq!(time_series.mean(), Some(4.0 / 3.0));
+        // intervals starting between changes pick up the level in force
+        assert_eq!(time_series.integral(5, 11), Some(8.0));
+        assert_eq!(time_series.time_weighted_mean(11, 13), Some(1.5));
+        assert_eq!(time_series.integral(5, 5), None);
+    }
+
+    #[test]
+    fn level_series_is_unknown_before_first_point() {
+        let time_series = series_of(SeriesKind::Level, &[(10, 2.0)]);
+        assert_eq!(time_series.integral(0, 20), Some(20.0));
+        assert_eq!(time_series.time_weighted_mean(0, 20), Some(2.0));
+        assert_eq!(time_series.integral(0, 10), None);
     }
 
     #[test]